@@ -13,10 +13,14 @@ fn bench_n_to_bits(c: &mut Criterion) {
     group.throughput(Throughput::Bytes(40000));
 
     group.bench_function("n_to_bits_lut", |b| b.iter(|| n_to_bits_lut(&n)));
-    group.bench_function("n_to_bits_pext", |b| b.iter(|| n_to_bits_pext(&n)));
-    group.bench_function("n_to_bits_shift", |b| b.iter(|| n_to_bits_shift(&n)));
-    group.bench_function("n_to_bits_movemask", |b| b.iter(|| n_to_bits_movemask(&n)));
-    group.bench_function("n_to_bits_mul", |b| b.iter(|| n_to_bits_mul(&n)));
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    group.bench_function("n_to_bits_pext", |b| b.iter(|| unsafe {n_to_bits_pext(&n)}));
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    group.bench_function("n_to_bits_shift", |b| b.iter(|| unsafe {n_to_bits_shift(&n)}));
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    group.bench_function("n_to_bits_movemask", |b| b.iter(|| unsafe {n_to_bits_movemask(&n)}));
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    group.bench_function("n_to_bits_mul", |b| b.iter(|| unsafe {n_to_bits_mul(&n)}));
     group.bench_function("memcpy", |b| b.iter(|| unsafe {let mut dest = vec![0u8; n.len()]; ptr::copy_nonoverlapping(n.as_ptr(), dest.as_mut_ptr(), n.len()); dest}));
 
     group.finish();
@@ -29,7 +33,8 @@ fn bench_n_to_bits2(c: &mut Criterion) {
     group.throughput(Throughput::Bytes(40000));
 
     group.bench_function("n_to_bits2_lut", |b| b.iter(|| n_to_bits2_lut(&n)));
-    group.bench_function("n_to_bits2_pext", |b| b.iter(|| n_to_bits2_pext(&n)));
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    group.bench_function("n_to_bits2_pext", |b| b.iter(|| unsafe {n_to_bits2_pext(&n)}));
 
     group.finish();
 }
@@ -42,9 +47,12 @@ fn bench_bits_to_n(c: &mut Criterion) {
     group.throughput(Throughput::Bytes(40000));
 
     group.bench_function("bits_to_n_lut", |b| b.iter(|| bits_to_n_lut(&bits, len)));
-    group.bench_function("bits_to_n_shuffle", |b| b.iter(|| bits_to_n_shuffle(&bits, len)));
-    group.bench_function("bits_to_n_pdep", |b| b.iter(|| bits_to_n_pdep(&bits, len)));
-    group.bench_function("bits_to_n_clmul", |b| b.iter(|| bits_to_n_clmul(&bits, len)));
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    group.bench_function("bits_to_n_shuffle", |b| b.iter(|| unsafe {bits_to_n_shuffle(&bits, len)}));
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    group.bench_function("bits_to_n_pdep", |b| b.iter(|| unsafe {bits_to_n_pdep(&bits, len)}));
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    group.bench_function("bits_to_n_clmul", |b| b.iter(|| unsafe {bits_to_n_clmul(&bits, len)}));
 
     group.finish();
 }
@@ -57,7 +65,8 @@ fn bench_bits_to_n2(c: &mut Criterion) {
     group.throughput(Throughput::Bytes(40000));
 
     group.bench_function("bits_to_n2_lut", |b| b.iter(|| bits_to_n2_lut(&bits, len)));
-    group.bench_function("bits_to_n2_pdep", |b| b.iter(|| bits_to_n2_pdep(&bits, len)));
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    group.bench_function("bits_to_n2_pdep", |b| b.iter(|| unsafe {bits_to_n2_pdep(&bits, len)}));
 
     group.finish();
 }
@@ -0,0 +1,215 @@
+use crate::packed::*;
+
+/// Get a cheap upper bound on the edit distance between two packed sequences, by trying every relative shift of up to
+/// `max_shift` nucleotides.
+///
+/// For each shift, the first nucleotides of one of the sequences are skipped, then the cost is the shift plus the
+/// Hamming distance over the overlap plus the number of nucleotides after the overlap. This is exact when the
+/// sequences only differ by substitutions and a single indel near one of the ends, so it is a quick filter before
+/// running a full alignment.
+pub fn approx_edit_bound(a: &[u64], b: &[u64], a_len: usize, b_len: usize, max_shift: usize) -> usize {
+    let mut res = a_len.max(b_len);
+
+    for s in 0..=max_shift {
+        if s <= a_len {
+            res = res.min(s + shifted_cost(&slice_bits(a, s, a_len - s), b, a_len - s, b_len));
+        }
+
+        if s > 0 && s <= b_len {
+            res = res.min(s + shifted_cost(a, &slice_bits(b, s, b_len - s), a_len, b_len - s));
+        }
+    }
+
+    res
+}
+
+/// Get the fraction of positions where two packed sequences of length `len` have the same nucleotide.
+///
+/// Two empty sequences are identical, so this returns 1.0 if `len` is zero.
+pub fn identity(a: &[u64], b: &[u64], len: usize) -> f32 {
+    if len == 0 {
+        return 1.0;
+    }
+
+    ((len - hamming_bits(a, b, len)) as f32) / (len as f32)
+}
+
+/// Build the match bitmasks of a packed sequence with at most 64 nucleotides, where bit `i` of the mask at index
+/// `code` is set if the nucleotide at index `i` has the two-bit encoding `code`.
+///
+/// This is the pattern table (`Peq`) for bit-parallel alignment algorithms.
+pub fn build_peq(bits: &[u64], len: usize) -> [u64; 4] {
+    if len > 64 {
+        panic!("The length must be at most 64!");
+    }
+
+    if len > (bits.len() << 5) {
+        panic!("The length is greater than the number of nucleotides!");
+    }
+
+    // split into the bit planes of the low and high bits of each field
+    let mut lo = 0u64;
+    let mut hi = 0u64;
+
+    for (i, &w) in bits[..words(len)].iter().enumerate() {
+        lo |= compact_fields(w) << (i << 5);
+        hi |= compact_fields(w >> 1) << (i << 5);
+    }
+
+    let mask = if len == 64 {!0} else {(1u64 << len) - 1};
+    [!lo & !hi & mask, lo & !hi & mask, !lo & hi & mask, lo & hi & mask]
+}
+
+// gather the low bit of each field into the low 32 bits
+#[inline]
+fn compact_fields(w: u64) -> u64 {
+    let mut x = w & LO_BITS;
+    x = (x | (x >> 1)) & 0x3333333333333333;
+    x = (x | (x >> 2)) & 0x0F0F0F0F0F0F0F0F;
+    x = (x | (x >> 4)) & 0x00FF00FF00FF00FF;
+    x = (x | (x >> 8)) & 0x0000FFFF0000FFFF;
+    (x | (x >> 16)) & 0x00000000FFFFFFFF
+}
+
+/// Get the edit (Levenshtein) distance between two packed sequences, by using Myers' bit-parallel algorithm.
+///
+/// The first sequence can have at most 64 nucleotides, while the second sequence can have any length.
+pub fn myers_edit_distance(a_bits: &[u64], a_len: usize, b_bits: &[u64], b_len: usize) -> usize {
+    if a_len > 64 {
+        panic!("The length of the first sequence must be at most 64!");
+    }
+
+    if b_len > (b_bits.len() << 5) {
+        panic!("The length is greater than the number of nucleotides!");
+    }
+
+    if a_len == 0 {
+        return b_len;
+    }
+
+    let peq = build_peq(a_bits, a_len);
+    let high = 1u64 << (a_len - 1);
+    // vertical deltas of the DP matrix, which are all +1 in the first column
+    let mut pv = if a_len == 64 {!0} else {(1u64 << a_len) - 1};
+    let mut mv = 0u64;
+    let mut score = a_len;
+
+    for j in 0..b_len {
+        let eq = peq[get_base(b_bits, j) as usize];
+        let xv = eq | mv;
+        let xh = ((eq & pv).wrapping_add(pv) ^ pv) | eq;
+        let mut ph = mv | !(xh | pv);
+        let mut mh = pv & xh;
+
+        if ph & high != 0 {
+            score += 1;
+        }
+
+        if mh & high != 0 {
+            score -= 1;
+        }
+
+        // the first row increases by one in each column, since the distance is global
+        ph = (ph << 1) | 1;
+        mh <<= 1;
+        pv = mh | !(xv | ph);
+        mv = ph & xv;
+    }
+
+    score
+}
+
+#[inline]
+fn shifted_cost(a: &[u64], b: &[u64], a_len: usize, b_len: usize) -> usize {
+    let overlap = a_len.min(b_len);
+    hamming_bits(a, b, overlap) + (a_len - overlap) + (b_len - overlap)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::n_to_bits::*;
+
+    #[test]
+    fn test_approx_edit_bound() {
+        let a = b"ATCGATCGATCGATCGATCGATCGATCGATCGGGCCATCG";
+        let b = b"GATCGATCGATCGATCGATCGATCGATCGATCGGGCCATCG";
+        let (a_bits, b_bits) = (n_to_bits_lut(a), n_to_bits_lut(b));
+        // the insertion shifts every nucleotide, so there is no cheap bound without a shift
+        assert!(approx_edit_bound(&a_bits, &b_bits, a.len(), b.len(), 0) > 10);
+        assert_eq!(approx_edit_bound(&a_bits, &b_bits, a.len(), b.len(), 1), 1);
+        assert_eq!(approx_edit_bound(&b_bits, &a_bits, b.len(), a.len(), 3), 1);
+        assert_eq!(approx_edit_bound(&a_bits, &a_bits, a.len(), a.len(), 3), 0);
+        assert_eq!(approx_edit_bound(&a_bits, &[], a.len(), 0, 3), a.len());
+    }
+
+    #[test]
+    fn test_identity() {
+        let a = n_to_bits_lut(b"ATCGATCGATCGATCGATCGATCGATCGATCGATCG");
+        let b = n_to_bits_lut(b"ATCGATCGATCGATCGATGCTAGCTAGCTAGCTAGC");
+        let c = n_to_bits_lut(b"GCTAGCTAGCTAGCTAGCTAGCTAGCTAGCTAGCTA");
+        assert_eq!(identity(&a, &a, 36), 1.0);
+        assert_eq!(identity(&a, &b, 36), 0.5);
+        assert_eq!(identity(&a, &c, 36), 0.0);
+        assert_eq!(identity(&[], &[], 0), 1.0);
+    }
+
+    #[test]
+    fn test_build_peq() {
+        let n = b"ATCGGATCGATCCATCGATCGATCGTTCGATCGATCGATCGATCGAACGATCGATCGATCGATG";
+
+        for len in [0, 5, 32, 33, 64] {
+            let peq = build_peq(&n_to_bits_lut(n), len);
+
+            for (code, &c) in [b'A', b'C', b'T', b'G'].iter().enumerate() {
+                let expected = (0..len).filter(|&i| n[i] == c).fold(0u64, |acc, i| acc | (1 << i));
+                assert_eq!(peq[code], expected);
+            }
+        }
+    }
+
+    fn naive_edit_distance(a: &[u8], b: &[u8]) -> usize {
+        let mut prev = (0..=b.len()).collect::<Vec<_>>();
+
+        for i in 1..=a.len() {
+            let mut curr = vec![i; b.len() + 1];
+
+            for j in 1..=b.len() {
+                let sub = prev[j - 1] + if a[i - 1] == b[j - 1] {0} else {1};
+                curr[j] = sub.min(prev[j] + 1).min(curr[j - 1] + 1);
+            }
+
+            prev = curr;
+        }
+
+        prev[b.len()]
+    }
+
+    #[test]
+    fn test_myers_edit_distance() {
+        let dist = |a: &[u8], b: &[u8]| myers_edit_distance(&n_to_bits_lut(a), a.len(), &n_to_bits_lut(b), b.len());
+        assert_eq!(dist(b"ATCGATCG", b"ATCGATCG"), 0);
+        // substitution
+        assert_eq!(dist(b"ATCGATCG", b"ATCCATCG"), 1);
+        // insertion
+        assert_eq!(dist(b"ATCGATCG", b"ATCGGATCG"), 1);
+        // deletion
+        assert_eq!(dist(b"ATCGATCG", b"ATCATCG"), 1);
+        assert_eq!(dist(b"", b"ATCG"), 4);
+        assert_eq!(dist(b"ATCG", b""), 4);
+
+        let seqs: [&[u8]; 5] = [
+            b"ATCGATCGATCGATCGATCGATCGATCGATCGATCGATCGATCGATCGATCGATCGATCGATCG",
+            b"ATCGATCGATGGATCGATCGATCGATCGATCGTTCGATCGATCGATCGATCATCGATCGATCGAAAA",
+            b"GGGGCCCCAAAATTTT",
+            b"TCGATCG",
+            b""
+        ];
+
+        for a in seqs.iter().filter(|a| a.len() <= 64) {
+            for b in &seqs {
+                assert_eq!(dist(a, b), naive_edit_distance(a, b));
+            }
+        }
+    }
+}
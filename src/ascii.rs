@@ -0,0 +1,365 @@
+#[cfg(target_arch = "x86")]
+use std::arch::x86::*;
+#[cfg(target_arch = "x86_64")]
+use std::arch::x86_64::*;
+
+use crate::features::has_avx2;
+
+static COMPLEMENT_LUT: [u8; 256] = {
+    let mut lut = [0u8; 256];
+    let mut i = 0;
+    while i < 256 {
+        lut[i] = i as u8;
+        i += 1;
+    }
+    lut[b'a' as usize] = b't';
+    lut[b't' as usize] = b'a';
+    lut[b'u' as usize] = b'a';
+    lut[b'c' as usize] = b'g';
+    lut[b'g' as usize] = b'c';
+    lut[b'A' as usize] = b'T';
+    lut[b'T' as usize] = b'A';
+    lut[b'U' as usize] = b'A';
+    lut[b'C' as usize] = b'G';
+    lut[b'G' as usize] = b'C';
+    lut
+};
+
+/// Complement each nucleotide in the byte string (`A <-> T/U` and `C <-> G`), without reversing it.
+///
+/// The case of each nucleotide is kept, and any other bytes (like `N`) are passed through.
+pub fn complement_ascii(n: &[u8]) -> Vec<u8> {
+    n.iter().map(|&c| COMPLEMENT_LUT[c as usize]).collect()
+}
+
+/// Reverse complement the byte string (`A <-> T/U` and `C <-> G`), by using a vectorized method with the `shuffle`
+/// instruction both for reversing and for complementing.
+///
+/// The case of each nucleotide is kept, and any other bytes (like `N`) are passed through, like in `complement_ascii`.
+///
+/// Falls back to a scalar method if the running CPU does not support AVX2.
+pub fn revcomp_ascii(n: &[u8]) -> Vec<u8> {
+    let len = n.len();
+    let end_idx = if has_avx2() {len >> 5} else {0};
+    let mut res = vec![0u8; len];
+
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    if end_idx > 0 {
+        unsafe {revcomp_avx2(n, &mut res)};
+    }
+
+    for i in (end_idx << 5)..len {
+        res[i] = COMPLEMENT_LUT[n[len - 1 - i] as usize];
+    }
+
+    res
+}
+
+// reverse complements every full 32-byte chunk from the end of the byte string
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "avx2")]
+unsafe fn revcomp_avx2(n: &[u8], res: &mut [u8]) {
+    let len = n.len();
+    let end_idx = len >> 5;
+
+    unsafe {
+        let ptr = n.as_ptr();
+        let res_ptr = res.as_mut_ptr() as *mut __m256i;
+
+        let reverse = _mm256_set_epi8(
+            0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15,
+            0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15
+        );
+        let lower_mask = _mm256_set1_epi8(0x20);
+        let lo_nibble = _mm256_set1_epi8(0x0F);
+        let hi_nibble = _mm256_set1_epi8(0xF0u8 as i8);
+        let row6 = _mm256_set1_epi8(0x60);
+        let row7 = _mm256_set1_epi8(0x70);
+        // the XOR that complements each letter, indexed by the low nibble, for `{a, c, g}` and `{t, u}`
+        let xor6 = _mm256_setr_epi8(
+            0, 0x15, 0, 0x04, 0, 0, 0, 0x04, 0, 0, 0, 0, 0, 0, 0, 0,
+            0, 0x15, 0, 0x04, 0, 0, 0, 0x04, 0, 0, 0, 0, 0, 0, 0, 0
+        );
+        let xor7 = _mm256_setr_epi8(
+            0, 0, 0, 0, 0x15, 0x14, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+            0, 0, 0, 0, 0x15, 0x14, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0
+        );
+
+        for i in 0..end_idx {
+            let v = _mm256_loadu_si256(ptr.add(len - ((i + 1) << 5)) as *const __m256i);
+            // reverse the bytes within each lane, then swap the lanes
+            let v = _mm256_permute4x64_epi64(_mm256_shuffle_epi8(v, reverse), 0b01001110);
+
+            // the case bit is not changed by the XOR
+            let lower = _mm256_or_si256(v, lower_mask);
+            let idx = _mm256_and_si256(lower, lo_nibble);
+            let row = _mm256_and_si256(lower, hi_nibble);
+            let x6 = _mm256_and_si256(_mm256_shuffle_epi8(xor6, idx), _mm256_cmpeq_epi8(row, row6));
+            let x7 = _mm256_and_si256(_mm256_shuffle_epi8(xor7, idx), _mm256_cmpeq_epi8(row, row7));
+
+            _mm256_storeu_si256(res_ptr.add(i), _mm256_xor_si256(v, _mm256_or_si256(x6, x7)));
+        }
+    }
+}
+
+/// Get the index of the first byte that is not one of `{A, T, U, C, G}` (in either case) in the byte string, by
+/// using a vectorized method with comparisons.
+///
+/// Falls back to a scalar method if the running CPU does not support AVX2.
+pub fn validate_dna(n: &[u8]) -> Option<usize> {
+    validate(n, false)
+}
+
+/// Get the index of the first byte that is not one of `{A, T, U, C, G, N}` (in either case) in the byte string, by
+/// using a vectorized method with comparisons.
+///
+/// Falls back to a scalar method if the running CPU does not support AVX2.
+pub fn validate_dna_n(n: &[u8]) -> Option<usize> {
+    validate(n, true)
+}
+
+#[inline]
+fn is_valid(c: u8, allow_n: bool) -> bool {
+    matches!(c | 0x20, b'a' | b't' | b'u' | b'c' | b'g') || (allow_n && (c | 0x20) == b'n')
+}
+
+#[inline]
+fn validate(n: &[u8], allow_n: bool) -> Option<usize> {
+    let end_idx = if has_avx2() {n.len() >> 5} else {0};
+
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    if end_idx > 0 {
+        if let Some(i) = unsafe {validate_avx2(n, allow_n)} {
+            return Some(i);
+        }
+    }
+
+    n[(end_idx << 5)..].iter().position(|&c| !is_valid(c, allow_n)).map(|i| (end_idx << 5) + i)
+}
+
+// only checks the full 32-byte chunks
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "avx2")]
+unsafe fn validate_avx2(n: &[u8], allow_n: bool) -> Option<usize> {
+    let ptr = n.as_ptr() as *const __m256i;
+    let end_idx = n.len() >> 5;
+
+    for i in 0..end_idx {
+        // each set bit is an invalid byte
+        let invalid = !valid_bits_avx2(_mm256_loadu_si256(ptr.add(i)), allow_n);
+
+        if invalid != 0 {
+            return Some((i << 5) + invalid.trailing_zeros() as usize);
+        }
+    }
+
+    None
+}
+
+// each set bit is a valid byte
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[inline]
+#[target_feature(enable = "avx2")]
+unsafe fn valid_bits_avx2(v: __m256i, allow_n: bool) -> u32 {
+    // setting the lowercase bit only maps uppercase letters to lowercase letters
+    let lower_mask = _mm256_set1_epi8(0x20);
+    let a = _mm256_set1_epi8(b'a' as i8);
+    let t = _mm256_set1_epi8(b't' as i8);
+    let u = _mm256_set1_epi8(b'u' as i8);
+    let c = _mm256_set1_epi8(b'c' as i8);
+    let g = _mm256_set1_epi8(b'g' as i8);
+    // duplicate a valid nucleotide if N is not allowed
+    let n_lower = _mm256_set1_epi8(if allow_n {b'n'} else {b'a'} as i8);
+
+    let v = _mm256_or_si256(v, lower_mask);
+    let at = _mm256_or_si256(_mm256_cmpeq_epi8(v, a), _mm256_cmpeq_epi8(v, t));
+    let uc = _mm256_or_si256(_mm256_cmpeq_epi8(v, u), _mm256_cmpeq_epi8(v, c));
+    let gn = _mm256_or_si256(_mm256_cmpeq_epi8(v, g), _mm256_cmpeq_epi8(v, n_lower));
+    _mm256_movemask_epi8(_mm256_or_si256(_mm256_or_si256(at, uc), gn)) as u32
+}
+
+/// Get the indices of the first and last bytes that are one of `{A, T, U, C, G}` (in either case) in the byte string,
+/// by using a vectorized method with comparisons, or `None` if there are no such bytes.
+///
+/// This is useful for trimming flanking `N`s or other ambiguous characters before encoding.
+///
+/// Falls back to a scalar method if the running CPU does not support AVX2.
+pub fn scan_extent(n: &[u8]) -> Option<(usize, usize)> {
+    let end_idx = if has_avx2() {n.len() >> 5} else {0};
+    let mut first = None;
+    let mut last = None;
+
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    if end_idx > 0 {
+        (first, last) = unsafe {scan_extent_avx2(n)};
+    }
+
+    for (i, &c) in n.iter().enumerate().skip(end_idx << 5) {
+        if is_valid(c, false) {
+            first = first.or(Some(i));
+            last = Some(i);
+        }
+    }
+
+    Some((first?, last?))
+}
+
+// only scans the full 32-byte chunks
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "avx2")]
+unsafe fn scan_extent_avx2(n: &[u8]) -> (Option<usize>, Option<usize>) {
+    let end_idx = n.len() >> 5;
+    let mut first = None;
+    let mut last = None;
+
+    unsafe {
+        let ptr = n.as_ptr() as *const __m256i;
+
+        for i in 0..end_idx {
+            let valid = valid_bits_avx2(_mm256_loadu_si256(ptr.add(i)), false);
+
+            if valid != 0 {
+                first = first.or(Some((i << 5) + valid.trailing_zeros() as usize));
+                last = Some((i << 5) + 31 - valid.leading_zeros() as usize);
+            }
+        }
+    }
+
+    (first, last)
+}
+
+/// Counts of each category of bytes in a byte string, ignoring case.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Composition {
+    pub a: usize,
+    pub c: usize,
+    pub g: usize,
+    /// Count of both `T` and `U`.
+    pub t: usize,
+    pub n: usize,
+    pub other: usize
+}
+
+/// Count the number of `{A, C, G, T/U, N}` bytes (in either case) and other bytes in the byte string, by using a
+/// vectorized method with comparisons and popcount.
+///
+/// Falls back to a scalar method if the running CPU does not support AVX2.
+pub fn composition(n: &[u8]) -> Composition {
+    let end_idx = if has_avx2() {n.len() >> 5} else {0};
+    let mut res = Composition::default();
+
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    if end_idx > 0 {
+        res = unsafe {composition_avx2(n)};
+    }
+
+    for &b in &n[(end_idx << 5)..] {
+        match b | 0x20 {
+            b'a' => res.a += 1,
+            b'c' => res.c += 1,
+            b'g' => res.g += 1,
+            b't' | b'u' => res.t += 1,
+            b'n' => res.n += 1,
+            _ => ()
+        }
+    }
+
+    res.other = n.len() - res.a - res.c - res.g - res.t - res.n;
+    res
+}
+
+// only counts the full 32-byte chunks, and leaves `other` as zero
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "avx2")]
+unsafe fn composition_avx2(n: &[u8]) -> Composition {
+    let end_idx = n.len() >> 5;
+    let mut res = Composition::default();
+
+    unsafe {
+        let ptr = n.as_ptr() as *const __m256i;
+        let lower_mask = _mm256_set1_epi8(0x20);
+        let a = _mm256_set1_epi8(b'a' as i8);
+        let c = _mm256_set1_epi8(b'c' as i8);
+        let g = _mm256_set1_epi8(b'g' as i8);
+        let t = _mm256_set1_epi8(b't' as i8);
+        let u = _mm256_set1_epi8(b'u' as i8);
+        let n_lower = _mm256_set1_epi8(b'n' as i8);
+
+        for i in 0..end_idx {
+            let v = _mm256_or_si256(_mm256_loadu_si256(ptr.add(i)), lower_mask);
+            let tu = _mm256_or_si256(_mm256_cmpeq_epi8(v, t), _mm256_cmpeq_epi8(v, u));
+
+            res.a += (_mm256_movemask_epi8(_mm256_cmpeq_epi8(v, a)) as u32).count_ones() as usize;
+            res.c += (_mm256_movemask_epi8(_mm256_cmpeq_epi8(v, c)) as u32).count_ones() as usize;
+            res.g += (_mm256_movemask_epi8(_mm256_cmpeq_epi8(v, g)) as u32).count_ones() as usize;
+            res.t += (_mm256_movemask_epi8(tu) as u32).count_ones() as usize;
+            res.n += (_mm256_movemask_epi8(_mm256_cmpeq_epi8(v, n_lower)) as u32).count_ones() as usize;
+        }
+    }
+
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_complement_ascii() {
+        assert_eq!(complement_ascii(b"ATCGU"), b"TAGCA");
+        assert_eq!(complement_ascii(b"atcgu"), b"tagca");
+        assert_eq!(complement_ascii(b"AtNnCg"), b"TaNnGc");
+    }
+
+    #[test]
+    fn test_revcomp_ascii() {
+        let n = b"ATCGATCGatcgatcgNNnnATCGUuATCGATCGATCGATCGAAGCTToqQ-ATCG";
+
+        for len in 0..n.len() {
+            let mut expected = complement_ascii(&n[..len]);
+            expected.reverse();
+            assert_eq!(revcomp_ascii(&n[..len]), expected);
+        }
+
+        assert_eq!(revcomp_ascii(b"AAtcGN"), b"NCgaTT");
+
+        let all = (0..=255u8).collect::<Vec<_>>();
+        let mut expected = complement_ascii(&all);
+        expected.reverse();
+        assert_eq!(revcomp_ascii(&all), expected);
+    }
+
+    #[test]
+    fn test_scan_extent() {
+        assert_eq!(scan_extent(b"NNATCGNN"), Some((2, 5)));
+        assert_eq!(scan_extent(b"NNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNtNNNNNNNNNNNNNNNNNNNNNNNNNNNNN"), Some((40, 40)));
+        assert_eq!(scan_extent(b"NNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNaN"), Some((69, 69)));
+        assert_eq!(scan_extent(b"ATCGNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNNcNNNNNNN"), Some((0, 61)));
+        assert_eq!(scan_extent(b"NNRY-"), None);
+        assert_eq!(scan_extent(b""), None);
+    }
+
+    #[test]
+    fn test_composition() {
+        assert_eq!(composition(b"ATCGNATCGX"), Composition{a: 2, c: 2, g: 2, t: 2, n: 1, other: 1});
+        assert_eq!(composition(b"ATCGATCGatcgatcgNNnnATCGUuATCGATCGATCGATCGAAGCTToqQ-ATCG"),
+                Composition{a: 12, c: 11, g: 11, t: 14, n: 4, other: 4});
+        assert_eq!(composition(b""), Composition::default());
+    }
+
+    #[test]
+    fn test_validate_dna() {
+        assert_eq!(validate_dna(b"ATCGATCGatcgatcgATCGATCGATCGATCGATCGUu"), None);
+        assert_eq!(validate_dna(b"ATCGATCGatcgatcgATCGATCGATCGATCGATCGN"), Some(36));
+        assert_eq!(validate_dna(b"ATCGATCGatcgatcgATNGATCGATCGATCGATCG"), Some(18));
+        assert_eq!(validate_dna(b""), None);
+    }
+
+    #[test]
+    fn test_validate_dna_n() {
+        assert_eq!(validate_dna_n(b"ATCGATCGatcgatcgATCGNNNNnnnnATCGATCGUuN"), None);
+        assert_eq!(validate_dna_n(b"ATCGATCGatcgatcgATCGATCGATC-ATCGATCG"), Some(27));
+        assert_eq!(validate_dna_n(b"ATCGATCGatcgatcgATCGATCGATCGATCGATC1"), Some(35));
+        assert_eq!(validate_dna_n(b"A\xe1TCGATCGatcgatcgATCGATCGATCGATCGA"), Some(1));
+    }
+}
@@ -0,0 +1,507 @@
+use crate::n_to_bits::n_to_bits_lut;
+use crate::packed::*;
+
+/// Database of nucleotide sequences that are encoded into pairs of bits and packed back to back into 64-bit integers.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PackedDb {
+    bits: Vec<u64>,
+    starts: Vec<usize>,
+    len: usize
+}
+
+impl PackedDb {
+    /// Create an empty database.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create an empty database with space for `words` 64-bit integers of packed nucleotides.
+    pub fn with_capacity(words: usize) -> Self {
+        PackedDb{bits: Vec::with_capacity(words), starts: Vec::new(), len: 0}
+    }
+
+    /// Encode a byte string of `{A, T/U, C, G}` and add it to the end of the database.
+    pub fn push(&mut self, n: &[u8]) {
+        append_bits(&mut self.bits, self.len, &n_to_bits_lut(n), n.len());
+        self.starts.push(self.len);
+        self.len += n.len();
+    }
+
+    /// Replace each sequence with its reverse complement.
+    pub fn reverse_complement_all(&mut self) {
+        let mut bits = Vec::with_capacity(self.bits.len());
+
+        for i in 0..self.len() {
+            let len = self.seq_len(i);
+            let rc = revcomp_bits(&slice_bits(&self.bits, self.starts[i], len), len);
+            append_bits(&mut bits, self.starts[i], &rc, len);
+        }
+
+        self.bits = bits;
+    }
+
+    /// Get the packed sequence at index `i` and its length.
+    pub fn get(&self, i: usize) -> (Vec<u64>, usize) {
+        let len = self.seq_len(i);
+        (slice_bits(&self.bits, self.starts[i], len), len)
+    }
+
+    /// Get the length of the sequence at index `i`.
+    pub fn seq_len(&self, i: usize) -> usize {
+        self.starts.get(i + 1).copied().unwrap_or(self.len) - self.starts[i]
+    }
+
+    /// Get the number of sequences.
+    pub fn len(&self) -> usize {
+        self.starts.len()
+    }
+
+    /// Check whether there are no sequences.
+    pub fn is_empty(&self) -> bool {
+        self.starts.is_empty()
+    }
+
+    /// Get the total number of nucleotides across all sequences.
+    pub fn total_len(&self) -> usize {
+        self.len
+    }
+
+    /// Get all of the packed nucleotides.
+    pub fn bits(&self) -> &[u64] {
+        &self.bits
+    }
+}
+
+/// Get the number of 64-bit integers needed to store all of the reads in a `PackedDb`.
+pub fn batch_word_capacity(reads: &[&[u8]]) -> usize {
+    words(reads.iter().map(|r| r.len()).sum())
+}
+
+/// Encode reads that all have length `read_len` into pairs of bits packed into 64-bit integers, where the packed bits
+/// of each read start every `stride_words` integers and unused integers are set to zero.
+///
+/// A stride that is a multiple of 8 keeps each read aligned to a 64 byte cache line.
+pub fn encode_strided(reads: &[&[u8]], read_len: usize, stride_words: usize) -> Vec<u64> {
+    if stride_words < words(read_len) {
+        panic!("The stride is less than the number of 64-bit integers needed for each read!");
+    }
+
+    let mut res = vec![0u64; reads.len() * stride_words];
+
+    for (i, r) in reads.iter().enumerate() {
+        if r.len() != read_len {
+            panic!("All of the reads must have the same length!");
+        }
+
+        let bits = n_to_bits_lut(r);
+        res[(i * stride_words)..(i * stride_words + bits.len())].copy_from_slice(&bits);
+    }
+
+    res
+}
+
+/// Count the nucleotides at each position across packed reads that all have length `len`.
+///
+/// The counts at each position are indexed by the two-bit encoding of each nucleotide, so the order is
+/// `[A, C, T, G]`.
+pub fn pwm_counts(seqs: &[(Vec<u64>, usize)], len: usize) -> Vec<[u32; 4]> {
+    let mut res = vec![[0u32; 4]; len];
+
+    for (bits, seq_len) in seqs {
+        if *seq_len != len {
+            panic!("All of the reads must have the same length!");
+        }
+
+        for (i, counts) in res.iter_mut().enumerate() {
+            counts[get_base(bits, i) as usize] += 1;
+        }
+    }
+
+    res
+}
+
+/// Get the packed consensus sequence and its length from per-position nucleotide counts (like from `pwm_counts`),
+/// by picking the most common nucleotide at each position.
+///
+/// Ties are broken by picking the nucleotide with the smallest two-bit encoding.
+pub fn consensus_from_pwm(counts: &[[u32; 4]]) -> (Vec<u64>, usize) {
+    let mut res = vec![0u64; words(counts.len())];
+
+    for (i, c) in counts.iter().enumerate() {
+        let mut best = 0;
+
+        for code in 1..4 {
+            if c[code] > c[best] {
+                best = code;
+            }
+        }
+
+        set_base(&mut res, i, best as u8);
+    }
+
+    (res, counts.len())
+}
+
+/// Merge two packed sequences of length `len` with per-position weights, by picking the nucleotide with the higher
+/// weight at each position where they are different.
+///
+/// Ties are broken by picking the nucleotide from `a`.
+pub fn weighted_consensus(a: &[u64], wa: &[u16], b: &[u64], wb: &[u16], len: usize) -> (Vec<u64>, usize) {
+    if len > (a.len() << 5) || len > (b.len() << 5) {
+        panic!("The length is greater than the number of nucleotides!");
+    }
+
+    if wa.len() < len || wb.len() < len {
+        panic!("There are less weights than nucleotides!");
+    }
+
+    let mut res = slice_bits(a, 0, len);
+
+    for i in 0..len {
+        if wb[i] > wa[i] {
+            set_base(&mut res, i, get_base(b, i));
+        }
+    }
+
+    (res, len)
+}
+
+/// Get the Shannon entropy (in bits) of the nucleotides at each position across packed reads that all have length
+/// `len`.
+///
+/// Conserved positions have low entropy, and the maximum entropy is 2 bits.
+pub fn positional_entropy(seqs: &[(Vec<u64>, usize)], len: usize) -> Vec<f32> {
+    pwm_counts(seqs, len)
+        .iter()
+        .map(|counts| {
+            let total = counts.iter().sum::<u32>() as f32;
+            counts
+                .iter()
+                .filter(|&&c| c > 0)
+                .map(|&c| {
+                    let p = (c as f32) / total;
+                    -p * p.log2()
+                })
+                .sum()
+        })
+        .collect()
+}
+
+/// Transpose packed reads that all have length `len` into a bit-sliced layout, where each group of 64 reads is stored
+/// as two bit planes for each position.
+///
+/// Element `g * len + i` holds the low bits and the high bits of the two-bit encodings at position `i` of reads
+/// `64 * g` to `64 * g + 63`, where bit `r` of each plane belongs to read `64 * g + r`. This allows operations over
+/// the same position in many reads at once.
+pub fn transpose_reads(seqs: &[(Vec<u64>, usize)], len: usize) -> Vec<[u64; 2]> {
+    let groups = (seqs.len() >> 6) + if seqs.len() & 63 == 0 {0} else {1};
+    let mut res = vec![[0u64; 2]; groups * len];
+
+    for (r, (bits, seq_len)) in seqs.iter().enumerate() {
+        if *seq_len != len {
+            panic!("All of the reads must have the same length!");
+        }
+
+        let planes = &mut res[((r >> 6) * len)..((r >> 6) * len + len)];
+
+        for (i, p) in planes.iter_mut().enumerate() {
+            let code = get_base(bits, i) as u64;
+            p[0] |= (code & 1) << (r & 63);
+            p[1] |= (code >> 1) << (r & 63);
+        }
+    }
+
+    res
+}
+
+/// Convert `n` reads of length `len` in the bit-sliced layout from `transpose_reads` back to packed reads.
+pub fn untranspose_reads(planes: &[[u64; 2]], n: usize, len: usize) -> Vec<(Vec<u64>, usize)> {
+    if planes.len() < ((n + 63) >> 6) * len {
+        panic!("There are not enough bit planes for the number of reads!");
+    }
+
+    (0..n)
+        .map(|r| {
+            let mut bits = vec![0u64; words(len)];
+            let group = &planes[((r >> 6) * len)..((r >> 6) * len + len)];
+
+            for (i, p) in group.iter().enumerate() {
+                let lo = (p[0] >> (r & 63)) & 1;
+                let hi = (p[1] >> (r & 63)) & 1;
+                set_base(&mut bits, i, (lo | (hi << 1)) as u8);
+            }
+
+            (bits, len)
+        })
+        .collect()
+}
+
+/// Get the Hamming distances between all pairs of packed reads that all have length `len`.
+///
+/// The distances are in the flattened lower triangle of the distance matrix, in the order
+/// `(1, 0), (2, 0), (2, 1), (3, 0), ...`, so the distance between reads `i` and `j < i` is at index
+/// `i * (i - 1) / 2 + j`.
+///
+/// The distances are stored as 16-bit integers, so `len` must be at most `u16::MAX`.
+pub fn hamming_matrix(seqs: &[(Vec<u64>, usize)], len: usize) -> Vec<u16> {
+    if seqs.iter().any(|(_, seq_len)| *seq_len != len) {
+        panic!("All of the reads must have the same length!");
+    }
+
+    if len > u16::MAX as usize {
+        panic!("The length of the reads must be at most 65535!");
+    }
+
+    let mut res = Vec::with_capacity(seqs.len() * seqs.len().saturating_sub(1) / 2);
+
+    for i in 1..seqs.len() {
+        for j in 0..i {
+            res.push(hamming_bits(&seqs[i].0, &seqs[j].0, len) as u16);
+        }
+    }
+
+    res
+}
+
+/// Get a histogram of the identities between all pairs of packed reads that all have length `len`, where the
+/// identity of each pair is the fraction of positions with the same nucleotide.
+///
+/// The range of identities `[0, 1]` is split into `bins` bins of equal width, and an identity of 1 is counted in the
+/// last bin.
+pub fn pairwise_identity_histogram(seqs: &[(Vec<u64>, usize)], len: usize, bins: usize) -> Vec<u32> {
+    if bins == 0 {
+        panic!("The number of bins must be greater than zero!");
+    }
+
+    if seqs.iter().any(|(_, seq_len)| *seq_len != len) {
+        panic!("All of the reads must have the same length!");
+    }
+
+    let mut res = vec![0u32; bins];
+
+    // the distances are not limited to 16 bits like in `hamming_matrix`, so long reads are allowed
+    for i in 1..seqs.len() {
+        for j in 0..i {
+            let d = hamming_bits(&seqs[i].0, &seqs[j].0, len);
+            // use integers to avoid rounding errors at the bin edges, and empty reads are identical
+            let bin = ((len - d) * bins).checked_div(len).unwrap_or(bins).min(bins - 1);
+            res[bin] += 1;
+        }
+    }
+
+    res
+}
+
+/// Estimate the sequencing error rate from pairs of packed reads from opposite strands, where the last `overlap_len`
+/// nucleotides of the first read are expected to be the reverse complement of the last `overlap_len` nucleotides of
+/// the second read.
+///
+/// Returns the fraction of positions in the overlaps where the two reads disagree, or zero if there are no positions.
+#[allow(clippy::type_complexity)]
+pub fn estimate_error_rate(pairs: &[((Vec<u64>, usize), (Vec<u64>, usize))], overlap_len: usize) -> f32 {
+    let mut mismatches = 0;
+
+    for ((a, a_len), (b, b_len)) in pairs {
+        if overlap_len > *a_len || overlap_len > *b_len {
+            panic!("The overlap length is greater than the length of a read!");
+        }
+
+        let a_overlap = slice_bits(a, a_len - overlap_len, overlap_len);
+        let b_overlap = revcomp_bits(&slice_bits(b, b_len - overlap_len, overlap_len), overlap_len);
+        mismatches += hamming_bits(&a_overlap, &b_overlap, overlap_len);
+    }
+
+    let total = pairs.len() * overlap_len;
+
+    if total == 0 {
+        return 0.0;
+    }
+
+    (mismatches as f32) / (total as f32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::n_to_bits::*;
+
+    fn pack(n: &[u8]) -> (Vec<u64>, usize) {
+        (n_to_bits_lut(n), n.len())
+    }
+
+    fn reads() -> Vec<&'static [u8]> {
+        vec![b"ATCG", b"GGCATCGATCGATCGATCGATCGATCGATCGATCGA", b"", b"TTTTTTTTTTTTTTTTTTTTTTTTTTTTTTTC", b"CA"]
+    }
+
+    #[test]
+    fn test_packed_db() {
+        let mut db = PackedDb::new();
+        assert!(db.is_empty());
+
+        for r in reads() {
+            db.push(r);
+        }
+
+        assert_eq!(db.len(), 5);
+        assert_eq!(db.total_len(), 74);
+
+        for (i, r) in reads().iter().enumerate() {
+            let (bits, len) = db.get(i);
+            assert_eq!(len, r.len());
+            assert_eq!(bits_to_n_lut(&bits, len), r.to_vec());
+        }
+    }
+
+    #[test]
+    fn test_reverse_complement_all() {
+        let mut db = PackedDb::new();
+
+        for r in reads() {
+            db.push(r);
+        }
+
+        db.reverse_complement_all();
+
+        for (i, r) in reads().iter().enumerate() {
+            let (bits, len) = db.get(i);
+            let rc = r.iter().rev().map(|&c| match c {b'A' => b'T', b'T' => b'A', b'C' => b'G', _ => b'C'}).collect::<Vec<_>>();
+            assert_eq!(bits_to_n_lut(&bits, len), rc);
+        }
+    }
+
+    #[test]
+    fn test_batch_word_capacity() {
+        let cap = batch_word_capacity(&reads());
+        assert_eq!(cap, 3);
+
+        let mut db = PackedDb::with_capacity(cap);
+
+        for r in reads() {
+            db.push(r);
+        }
+
+        assert_eq!(db.bits().len(), cap);
+        assert_eq!(batch_word_capacity(&[]), 0);
+    }
+
+    #[test]
+    fn test_encode_strided() {
+        let reads: Vec<&[u8]> = vec![b"ATCGATCGATCGATCGATCGATCGATCGATCGAA", b"GGCCGGCCGGCCGGCCGGCCGGCCGGCCGGCCTT"];
+        let bits = encode_strided(&reads, 34, 8);
+        assert_eq!(bits.len(), 16);
+
+        for (i, r) in reads.iter().enumerate() {
+            assert_eq!(bits_to_n_lut(&bits[(i * 8)..], 34), r.to_vec());
+            assert!(bits[(i * 8 + 2)..(i * 8 + 8)].iter().all(|&w| w == 0));
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_encode_strided_small_stride() {
+        encode_strided(&[b"ATCGATCGATCGATCGATCGATCGATCGATCGAA"], 34, 1);
+    }
+
+    #[test]
+    fn test_pwm_counts() {
+        let seqs = vec![pack(b"ATCG"), pack(b"ATCC"), pack(b"GTAC")];
+        assert_eq!(pwm_counts(&seqs, 4), vec![[2, 0, 0, 1], [0, 0, 3, 0], [1, 2, 0, 0], [0, 2, 0, 1]]);
+        assert_eq!(pwm_counts(&[], 2), vec![[0; 4]; 2]);
+    }
+
+    #[test]
+    fn test_consensus_from_pwm() {
+        let seqs = vec![pack(b"ATCGATCGATCGATCGATCGATCGATCGATCGAT"), pack(b"ATCCATCGATCGATCGATCGATCGATCGATCGAT"),
+                pack(b"GTACATCGATCGATCGATCGATCGATCGATCGTA")];
+        let (bits, len) = consensus_from_pwm(&pwm_counts(&seqs, 34));
+        assert_eq!(bits_to_n_lut(&bits, len), b"ATCCATCGATCGATCGATCGATCGATCGATCGAT".to_vec());
+
+        // ties pick A before C before T before G
+        assert_eq!(consensus_from_pwm(&[[1, 1, 0, 0], [0, 0, 2, 2], [0, 0, 0, 0]]), (vec![0b001000], 3));
+    }
+
+    #[test]
+    fn test_positional_entropy() {
+        let seqs = vec![pack(b"AAT"), pack(b"ACT"), pack(b"ATT"), pack(b"AGA")];
+        let entropy = positional_entropy(&seqs, 3);
+        assert_eq!(entropy[0], 0.0);
+        assert!((entropy[1] - 2.0).abs() < 1e-6);
+        assert!((entropy[2] - 0.8112781).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_transpose_reads() {
+        let seqs = vec![pack(b"ATCG"), pack(b"GGCA"), pack(b"TTTT")];
+        let planes = transpose_reads(&seqs, 4);
+        assert_eq!(planes, vec![[0b010, 0b110], [0b010, 0b111], [0b011, 0b100], [0b001, 0b101]]);
+        assert_eq!(untranspose_reads(&planes, 3, 4), seqs);
+
+        let seqs = (0..100).map(|i| pack(&b"ATCGATCGATCGATCGATCGATCGATCGATCGATCGATCG"[(i % 4)..(i % 4 + 33)])).collect::<Vec<_>>();
+        let planes = transpose_reads(&seqs, 33);
+        assert_eq!(planes.len(), 66);
+        assert_eq!(untranspose_reads(&planes, 100, 33), seqs);
+    }
+
+    #[test]
+    fn test_weighted_consensus() {
+        let (a, len) = pack(b"ATCGATCGATCGATCGATCGATCGATCGATCGATCG");
+        let (b, _) = pack(b"ATCGTTCGATCGATCGATCGATCGATCCCTCGATCG");
+        let mut wa = vec![10u16; len];
+        let wb = vec![20u16; len];
+        assert_eq!(weighted_consensus(&a, &wa, &b, &wb, len), (b.clone(), len));
+
+        wa[4] = 30;
+        wa[28] = 20;
+        assert_eq!(weighted_consensus(&a, &wa, &b, &wb, len), pack(b"ATCGATCGATCGATCGATCGATCGATCCATCGATCG"));
+        assert_eq!(weighted_consensus(&a, &wa, &b, &wb, 0), (vec![], 0));
+    }
+
+    #[test]
+    fn test_hamming_matrix() {
+        let seqs = vec![pack(b"ATCGATCG"), pack(b"ATCGTTCG"), pack(b"GGCGTTCG")];
+        assert_eq!(hamming_matrix(&seqs, 8), vec![1, 3, 2]);
+        assert_eq!(hamming_matrix(&seqs[..1], 8), vec![]);
+
+        let len = u16::MAX as usize;
+        assert_eq!(hamming_matrix(&[pack(&vec![b'A'; len]), pack(&vec![b'C'; len])], len), vec![u16::MAX]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_hamming_matrix_too_long() {
+        let len = u16::MAX as usize + 1;
+        hamming_matrix(&[pack(&vec![b'A'; len]), pack(&vec![b'C'; len])], len);
+    }
+
+    #[test]
+    fn test_pairwise_identity_histogram() {
+        // the identities are 0.875, 0.625, and 0.75
+        let seqs = vec![pack(b"ATCGATCG"), pack(b"ATCGTTCG"), pack(b"GGCGTTCG")];
+        assert_eq!(pairwise_identity_histogram(&seqs, 8, 4), vec![0, 0, 1, 2]);
+        assert_eq!(pairwise_identity_histogram(&seqs, 8, 8), vec![0, 0, 0, 0, 0, 1, 1, 1]);
+        assert_eq!(pairwise_identity_histogram(&[pack(b"ATCG"), pack(b"ATCG")], 4, 2), vec![0, 1]);
+        assert_eq!(pairwise_identity_histogram(&seqs[..1], 8, 2), vec![0, 0]);
+
+        // the distance does not fit in 16 bits
+        let len = 70000;
+        let half = [vec![b'A'; len / 2], vec![b'C'; len / 2]].concat();
+        let seqs = vec![pack(&vec![b'A'; len]), pack(&vec![b'C'; len]), pack(&half)];
+        assert_eq!(pairwise_identity_histogram(&seqs, len, 2), vec![1, 2]);
+    }
+
+    #[test]
+    fn test_estimate_error_rate() {
+        let a = b"CCGTAATGCCTTTCCCTAACAGAGTTTTTCGAACTCGTGT";
+        let b = b"TCTAATTCCGTCGCTCGACAACACGAGTTCGAAAAACTCT";
+        // two errors in the overlap
+        let a_err = b"CCGTAATGCCTTTCCCTAACAGAGTATTTCAAACTCGTGT";
+        // one error outside of the overlap
+        let a_outside = b"CCGTACTGCCTTTCCCTAACAGAGTTTTTCGAACTCGTGT";
+
+        let pairs = vec![(pack(a), pack(b)), (pack(a_err), pack(b)), (pack(a_outside), pack(b))];
+        assert_eq!(estimate_error_rate(&pairs, 20), 2.0 / 60.0);
+        assert_eq!(estimate_error_rate(&pairs[..1], 20), 0.0);
+        assert_eq!(estimate_error_rate(&pairs, 0), 0.0);
+        assert_eq!(estimate_error_rate(&[], 20), 0.0);
+    }
+}
@@ -0,0 +1,73 @@
+use crate::n_to_bits::bits_to_n_lut;
+use crate::packed::*;
+
+/// Nucleotide sequence that is mostly `{A, C, T, G}`, stored as pairs of bits packed into 64-bit integers with a
+/// sorted list of the positions and original bytes of every other character.
+///
+/// This is more compact than `n_to_bits2_lut` when there are very few `N`s, lowercase, or IUPAC characters.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CompactSeq {
+    /// Packed nucleotides, with every exception encoded as `A`.
+    pub bits: Vec<u64>,
+    /// Number of nucleotides.
+    pub len: usize,
+    /// Index and original byte of each character that is not one of `{A, C, T, G}`, sorted by index.
+    pub exceptions: Vec<(usize, u8)>
+}
+
+/// Encode a byte string into a `CompactSeq`, where any byte that is not one of `{A, C, T, G}` is stored as an
+/// exception.
+pub fn encode_compact(n: &[u8]) -> CompactSeq {
+    let mut bits = vec![0u64; words(n.len())];
+    let mut exceptions = Vec::new();
+
+    for (i, &c) in n.iter().enumerate() {
+        let code = match c {
+            b'A' => 0b00,
+            b'T' => 0b10,
+            b'C' => 0b01,
+            b'G' => 0b11,
+            _ => {
+                exceptions.push((i, c));
+                continue;
+            }
+        };
+
+        set_base(&mut bits, i, code);
+    }
+
+    CompactSeq{bits, len: n.len(), exceptions}
+}
+
+/// Decode a `CompactSeq` back into the original byte string.
+pub fn decode_compact(seq: &CompactSeq) -> Vec<u8> {
+    let mut res = bits_to_n_lut(&seq.bits, seq.len);
+
+    for &(i, c) in &seq.exceptions {
+        res[i] = c;
+    }
+
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::n_to_bits::*;
+
+    #[test]
+    fn test_encode_compact() {
+        let seq = encode_compact(b"ATCGNATCGATCGATCGATCGATCGATCGATCGaTC");
+        assert_eq!(seq.len, 36);
+        assert_eq!(seq.exceptions, vec![(4, b'N'), (33, b'a')]);
+        assert_eq!(seq.bits, n_to_bits_lut(b"ATCGAATCGATCGATCGATCGATCGATCGATCGATC"));
+        assert_eq!(encode_compact(b""), CompactSeq::default());
+    }
+
+    #[test]
+    fn test_decode_compact() {
+        for n in &[&b"ATCGNATCGATCGATCGATCGATCGATCGATCGaTC"[..], b"URYKMSWBDHVNacgtn-", b"ATCG", b""] {
+            assert_eq!(decode_compact(&encode_compact(n)), n.to_vec());
+        }
+    }
+}
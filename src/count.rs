@@ -0,0 +1,412 @@
+use crate::kmer::*;
+use crate::packed::*;
+
+/// Count the number of each nucleotide in the range `[start, end)` of a packed sequence.
+///
+/// The counts are indexed by the two-bit encoding of each nucleotide, so the order is `[A, C, T, G]`.
+pub fn ranged_base_counts(bits: &[u64], len: usize, start: usize, end: usize) -> [usize; 4] {
+    if len > (bits.len() << 5) {
+        panic!("The length is greater than the number of nucleotides!");
+    }
+
+    if start > end || end > len {
+        panic!("The range is out of bounds!");
+    }
+
+    let mut res = [0usize; 4];
+
+    if start == end {
+        return res;
+    }
+
+    for (i, &w) in bits.iter().enumerate().take(words(end)).skip(start >> 5) {
+        let mask = range_mask(start, end, i);
+        // split into the low and high bits of each field
+        let lo = w & mask;
+        let hi = (w >> 1) & mask;
+
+        res[0b00] += (mask & !lo & !hi).count_ones() as usize;
+        res[0b01] += (lo & !hi).count_ones() as usize;
+        res[0b10] += (!lo & hi).count_ones() as usize;
+        res[0b11] += (lo & hi).count_ones() as usize;
+    }
+
+    res
+}
+
+/// Get a mask of the nucleotides that appear in the range `[start, end)` of a packed sequence, where bit `i` is set if
+/// the nucleotide with the two-bit encoding `i` appears.
+pub fn alphabet_mask(bits: &[u64], len: usize, start: usize, end: usize) -> u8 {
+    if len > (bits.len() << 5) {
+        panic!("The length is greater than the number of nucleotides!");
+    }
+
+    if start > end || end > len {
+        panic!("The range is out of bounds!");
+    }
+
+    let mut res = 0u8;
+
+    if start == end {
+        return res;
+    }
+
+    for (i, &w) in bits.iter().enumerate().take(words(end)).skip(start >> 5) {
+        let mask = range_mask(start, end, i);
+
+        for code in 0..4 {
+            if match_fields(w, code) & mask != 0 {
+                res |= 1 << code;
+            }
+        }
+
+        // stop early once every nucleotide is found
+        if res == 0b1111 {
+            break;
+        }
+    }
+
+    res
+}
+
+/// Get the number of distinct nucleotides in each window of length `window` of a packed sequence, for every window
+/// start.
+///
+/// A window with only one distinct nucleotide is a homopolymer.
+pub fn window_distinct_bases(bits: &[u64], len: usize, window: usize) -> Vec<u8> {
+    if window == 0 {
+        panic!("The window size must be greater than zero!");
+    }
+
+    (0..(len + 1).saturating_sub(window)).map(|i| alphabet_mask(bits, len, i, i + window).count_ones() as u8).collect()
+}
+
+/// Count the number of each nucleotide in a packed sequence.
+///
+/// The counts are indexed by the two-bit encoding of each nucleotide, so the order is `[A, C, T, G]`.
+pub fn base_counts(bits: &[u64], len: usize) -> [usize; 4] {
+    ranged_base_counts(bits, len, 0, len)
+}
+
+/// Count the number of G and C nucleotides in a packed sequence.
+///
+/// With the two-bit encoding, a nucleotide is either G or C exactly when the low bit of its field is set.
+pub fn gc_count(bits: &[u64], len: usize) -> usize {
+    if len > (bits.len() << 5) {
+        panic!("The length is greater than the number of nucleotides!");
+    }
+
+    (0..words(len)).map(|i| (bits[i] & word_mask(len, i) & LO_BITS).count_ones() as usize).sum()
+}
+
+/// Count the number of each nucleotide at the positions `phase, phase + period, phase + 2 * period, ...` of a packed
+/// sequence.
+///
+/// For example, a period of 3 gives the composition at one of the codon positions. The counts are indexed by the
+/// two-bit encoding of each nucleotide, so the order is `[A, C, T, G]`.
+pub fn periodic_base_counts(bits: &[u64], len: usize, period: usize, phase: usize) -> [usize; 4] {
+    if len > (bits.len() << 5) {
+        panic!("The length is greater than the number of nucleotides!");
+    }
+
+    if period == 0 {
+        panic!("The period must be greater than zero!");
+    }
+
+    let mut res = [0usize; 4];
+
+    for i in (phase..len).step_by(period) {
+        res[get_base(bits, i) as usize] += 1;
+    }
+
+    res
+}
+
+/// Get the fraction of G and C nucleotides at the third position of each codon (GC3) in a packed coding sequence,
+/// where the first codon starts at index `frame`.
+///
+/// A sequence without any complete codons has a GC3 of zero.
+pub fn gc3_content(bits: &[u64], len: usize, frame: usize) -> f32 {
+    let counts = periodic_base_counts(bits, len, 3, frame + 2);
+    let total = counts.iter().sum::<usize>();
+
+    if total == 0 {
+        return 0.0;
+    }
+
+    ((counts[0b01] + counts[0b11]) as f32) / (total as f32)
+}
+
+/// Count the number of each codon in a packed coding sequence, where the first codon starts at index `frame`.
+///
+/// The counts are indexed by the packed codon, with the first nucleotide in the lowest two bits. An incomplete codon
+/// at the end is ignored.
+pub fn codon_counts(bits: &[u64], len: usize, frame: usize) -> [u32; 64] {
+    if len > (bits.len() << 5) {
+        panic!("The length is greater than the number of nucleotides!");
+    }
+
+    let mut res = [0u32; 64];
+
+    for i in (frame..len.saturating_sub(2)).step_by(3) {
+        res[kmer_at(bits, len, i, 3) as usize] += 1;
+    }
+
+    res
+}
+
+/// Get the two-bit encodings of the nucleotides in a packed sequence, ordered from the most frequent to the least
+/// frequent.
+///
+/// Nucleotides with the same count are ordered by their encoding.
+pub fn best_alphabet_permutation(bits: &[u64], len: usize) -> [u8; 4] {
+    let counts = base_counts(bits, len);
+    let mut res = [0b00, 0b01, 0b10, 0b11];
+    // the sort is stable, so ties keep the order of the encodings
+    res.sort_by(|&a, &b| counts[b as usize].cmp(&counts[a as usize]));
+    res
+}
+
+/// Estimate the melting temperature (in degrees Celsius) of a packed sequence.
+///
+/// Sequences shorter than 14 nucleotides use the Wallace rule `2 * (A + T) + 4 * (G + C)`, while longer sequences use
+/// `64.9 + 41 * (G + C - 16.4) / len`.
+pub fn melting_temp(bits: &[u64], len: usize) -> f32 {
+    let counts = base_counts(bits, len);
+    let at = (counts[0b00] + counts[0b10]) as f32;
+    let gc = (counts[0b01] + counts[0b11]) as f32;
+
+    if len < 14 {
+        2.0 * at + 4.0 * gc
+    } else {
+        64.9 + 41.0 * (gc - 16.4) / (len as f32)
+    }
+}
+
+/// Get the GC skew `(G - C) / (G + C)` of each window of length `window` in a packed sequence, where the windows start
+/// every `step` nucleotides.
+///
+/// Windows without any G or C have a skew of zero.
+pub fn gc_skew(bits: &[u64], len: usize, window: usize, step: usize) -> Vec<f32> {
+    if window == 0 || step == 0 {
+        panic!("The window size and step must be greater than zero!");
+    }
+
+    let mut res = Vec::new();
+    let mut start = 0;
+
+    while start + window <= len {
+        let counts = ranged_base_counts(bits, len, start, start + window);
+        let g = counts[0b11] as f32;
+        let c = counts[0b01] as f32;
+        res.push(if g + c == 0.0 {0.0} else {(g - c) / (g + c)});
+        start += step;
+    }
+
+    res
+}
+
+/// Packed `CG` dinucleotide.
+const CPG: u64 = 0b01 | (0b11 << 2);
+
+/// Count the number of CpG sites (`CG` dinucleotides) in a packed sequence.
+pub fn count_cpg(bits: &[u64], len: usize) -> usize {
+    count_kmer(bits, len, CPG, 2)
+}
+
+/// Find the CpG islands in a packed sequence, which are the merged ranges `[start, end)` of the windows of length
+/// `window` where the GC content is greater than `gc_thresh` and the observed to expected CpG ratio
+/// `CpG * window / (C * G)` is greater than `oe_thresh`.
+///
+/// The Gardiner-Garden and Frommer criteria use a window of 200, a GC threshold of 0.5, and a ratio threshold of 0.6.
+pub fn find_cpg_islands(bits: &[u64], len: usize, window: usize, gc_thresh: f32, oe_thresh: f32)
+        -> Vec<(usize, usize)> {
+    if window < 2 {
+        panic!("The window size must be at least 2!");
+    }
+
+    // prefix sums of the number of CpG sites that start before each index
+    let mut cpg = vec![0usize; len + 1];
+
+    for (i, kmer) in KmerIter::new(bits, len, 2).enumerate() {
+        cpg[i + 1] = cpg[i] + if kmer == CPG {1} else {0};
+    }
+
+    let mut res: Vec<(usize, usize)> = Vec::new();
+
+    for start in 0..(len + 1).saturating_sub(window) {
+        let end = start + window;
+        let counts = ranged_base_counts(bits, len, start, end);
+        let (c, g) = (counts[0b01] as f32, counts[0b11] as f32);
+        let gc = (c + g) / (window as f32);
+        let sites = (cpg[end - 1] - cpg[start]) as f32;
+        let oe = if c * g == 0.0 {0.0} else {sites * (window as f32) / (c * g)};
+
+        if gc <= gc_thresh || oe <= oe_thresh {
+            continue;
+        }
+
+        match res.last_mut() {
+            Some((_, e)) if *e >= start => *e = end,
+            _ => res.push((start, end))
+        }
+    }
+
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::n_to_bits::*;
+
+    fn naive_counts(n: &[u8]) -> [usize; 4] {
+        let mut res = [0usize; 4];
+
+        for &c in n {
+            res[match c {b'A' => 0, b'C' => 1, b'T' => 2, _ => 3}] += 1;
+        }
+
+        res
+    }
+
+    #[test]
+    fn test_ranged_base_counts() {
+        let n = b"AAGTCGATCGCCCCCGATCGATCGATCGTTTGGGATCGATCGATCGATCGATCGATCGATCGATCGTTTA";
+        let bits = n_to_bits_lut(n);
+
+        for &(start, end) in &[(0, n.len()), (0, 0), (3, 4), (5, 40), (32, 64), (31, 33), (40, n.len())] {
+            assert_eq!(ranged_base_counts(&bits, n.len(), start, end), naive_counts(&n[start..end]));
+        }
+    }
+
+    #[test]
+    fn test_alphabet_mask() {
+        let n = b"AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAATTTTCG";
+        let bits = n_to_bits_lut(n);
+        assert_eq!(alphabet_mask(&bits, n.len(), 0, n.len()), 0b1111);
+        assert_eq!(alphabet_mask(&bits, n.len(), 0, 35), 0b0001);
+        assert_eq!(alphabet_mask(&bits, n.len(), 30, 39), 0b0101);
+        assert_eq!(alphabet_mask(&bits, n.len(), 35, 35), 0);
+    }
+
+    #[test]
+    fn test_window_distinct_bases() {
+        let n = b"AAAAAATCGTT";
+        let bits = n_to_bits_lut(n);
+        assert_eq!(window_distinct_bases(&bits, n.len(), 4), vec![1, 1, 1, 2, 3, 4, 3, 3]);
+        assert_eq!(window_distinct_bases(&bits, n.len(), 11), vec![4]);
+        assert_eq!(window_distinct_bases(&bits, n.len(), 12), vec![]);
+    }
+
+    #[test]
+    fn test_base_counts() {
+        let n = b"AAGTCGATCGCCCCCGATCGATCGATCGTTTGGGATCGATCGATCGATCG";
+        assert_eq!(base_counts(&n_to_bits_lut(n), n.len()), naive_counts(n));
+        let bits = n_to_bits_lut(n);
+        assert_eq!(base_counts(&bits, n.len()), naive_counts(&bits_to_n_lut(&bits, n.len())));
+        // padding is ignored
+        assert_eq!(base_counts(&[!0], 3), [0, 0, 0, 3]);
+    }
+
+    #[test]
+    fn test_gc_count() {
+        let n = b"AAGTCGATCGCCCCCGATCGATCGATCGTTTGGGATCGATCGATCGATCG";
+        let bits = n_to_bits_lut(n);
+        let counts = naive_counts(&bits_to_n_lut(&bits, n.len()));
+        assert_eq!(gc_count(&bits, n.len()), counts[0b01] + counts[0b11]);
+        assert_eq!(gc_count(&bits, 5), 2);
+        // padding is ignored
+        assert_eq!(gc_count(&[!0], 3), 3);
+        assert_eq!(gc_count(&[!0], 0), 0);
+    }
+
+    #[test]
+    fn test_periodic_base_counts() {
+        let n = b"GATGCAGTCGGAGAAGTTGCCGACGGTGAGG";
+        let bits = n_to_bits_lut(n);
+        assert_eq!(periodic_base_counts(&bits, n.len(), 3, 0), [0, 0, 0, 11]);
+        assert_eq!(periodic_base_counts(&bits, n.len(), 3, 1), [4, 2, 2, 2]);
+        assert_eq!(periodic_base_counts(&bits, n.len(), 1, 0), base_counts(&bits, n.len()));
+        assert_eq!(periodic_base_counts(&bits, n.len(), 3, n.len()), [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_gc3_content() {
+        // the codons are ATG, GCC, AAA, CTG, TAA
+        let n = b"ATGGCCAAACTGTAA";
+        let bits = n_to_bits_lut(n);
+        assert_eq!(gc3_content(&bits, n.len(), 0), 0.6);
+        // the codons are TGG, CCA, AAC, TGT
+        assert_eq!(gc3_content(&bits, n.len(), 1), 0.5);
+        assert_eq!(gc3_content(&bits, 2, 0), 0.0);
+    }
+
+    #[test]
+    fn test_codon_counts() {
+        let n = b"ATGGCCAAACTGTAAATG";
+        let bits = n_to_bits_lut(n);
+        let codon = |c: &[u8]| n_to_bits_lut(c)[0] as usize;
+
+        let counts = codon_counts(&bits, n.len(), 0);
+        assert_eq!(counts[codon(b"ATG")], 2);
+        assert_eq!(counts[codon(b"GCC")], 1);
+        assert_eq!(counts[codon(b"TAA")], 1);
+        assert_eq!(counts[codon(b"GGC")], 0);
+        assert_eq!(counts.iter().sum::<u32>(), 6);
+
+        // the incomplete codon at the end is ignored
+        let counts = codon_counts(&bits, n.len(), 1);
+        assert_eq!(counts[codon(b"TGG")], 1);
+        assert_eq!(counts[codon(b"AAA")], 1);
+        assert_eq!(counts.iter().sum::<u32>(), 5);
+
+        assert_eq!(codon_counts(&bits, 2, 0), [0u32; 64]);
+    }
+
+    #[test]
+    fn test_best_alphabet_permutation() {
+        let n = b"GGGGGGTTTTAAAAAAAAAGGGGGGGC";
+        assert_eq!(best_alphabet_permutation(&n_to_bits_lut(n), n.len()), [0b11, 0b00, 0b10, 0b01]);
+        assert_eq!(best_alphabet_permutation(&n_to_bits_lut(b"TTCG"), 4), [0b10, 0b01, 0b11, 0b00]);
+        assert_eq!(best_alphabet_permutation(&[], 0), [0b00, 0b01, 0b10, 0b11]);
+    }
+
+    #[test]
+    fn test_melting_temp() {
+        assert_eq!(melting_temp(&n_to_bits_lut(b"ATCGGC"), 6), 20.0);
+        let n = b"ATCGATCGATCGATCGATCG";
+        assert!((melting_temp(&n_to_bits_lut(n), n.len()) - 51.78).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_gc_skew() {
+        let n = b"GGGGAAAAGGCCTTTTCCCCAAAAATTT";
+        let bits = n_to_bits_lut(n);
+        assert_eq!(gc_skew(&bits, n.len(), 8, 4), vec![1.0, 0.0, 0.0, -1.0, -1.0, 0.0]);
+        assert_eq!(gc_skew(&bits, n.len(), 12, 12), vec![0.5, -1.0]);
+        assert_eq!(gc_skew(&bits, n.len(), 29, 1), vec![]);
+    }
+
+    #[test]
+    fn test_count_cpg() {
+        assert_eq!(count_cpg(&n_to_bits_lut(b"ACGCGTTCGAGC"), 12), 3);
+        assert_eq!(count_cpg(&n_to_bits_lut(b"GCGC"), 4), 1);
+        assert_eq!(count_cpg(&n_to_bits_lut(b"C"), 1), 0);
+    }
+
+    #[test]
+    fn test_find_cpg_islands() {
+        let n = [b"ATTA".repeat(50), b"CGGC".repeat(25), b"ATTA".repeat(50)].concat();
+        let bits = n_to_bits_lut(&n);
+        let islands = find_cpg_islands(&bits, n.len(), 50, 0.5, 0.6);
+        assert_eq!(islands.len(), 1);
+        assert!(islands[0].0 >= 150 && islands[0].0 <= 200 && islands[0].1 >= 300 && islands[0].1 <= 350);
+
+        // GC rich but CpG poor
+        let n = b"GGCA".repeat(100);
+        assert_eq!(find_cpg_islands(&n_to_bits_lut(&n), n.len(), 50, 0.5, 0.6), vec![]);
+        let n = b"ATTA".repeat(100);
+        assert_eq!(find_cpg_islands(&n_to_bits_lut(&n), n.len(), 50, 0.5, 0.6), vec![]);
+    }
+}
@@ -0,0 +1,225 @@
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use crate::n_to_bits::*;
+use crate::n_to_bits2::*;
+
+const INIT: u8 = 1 << 0;
+const AVX2: u8 = 1 << 1;
+const BMI2: u8 = 1 << 2;
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+const SSSE3: u8 = 1 << 3;
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+const PCLMULQDQ: u8 = 1 << 4;
+
+// zero until the running CPU has been checked
+static DETECTED: AtomicU8 = AtomicU8::new(0);
+
+/// Get the names of the SIMD target features that were enabled when the crate was compiled.
+///
+/// These are features enabled with flags like `-C target-cpu=native`, not the features that the running CPU supports.
+pub fn compiled_features() -> Vec<&'static str> {
+    let mut res = Vec::new();
+
+    if cfg!(target_feature = "sse2") {
+        res.push("sse2");
+    }
+
+    if cfg!(target_feature = "ssse3") {
+        res.push("ssse3");
+    }
+
+    if cfg!(target_feature = "sse4.1") {
+        res.push("sse4.1");
+    }
+
+    if cfg!(target_feature = "avx") {
+        res.push("avx");
+    }
+
+    if cfg!(target_feature = "avx2") {
+        res.push("avx2");
+    }
+
+    if cfg!(target_feature = "bmi1") {
+        res.push("bmi1");
+    }
+
+    if cfg!(target_feature = "bmi2") {
+        res.push("bmi2");
+    }
+
+    if cfg!(target_feature = "pclmulqdq") {
+        res.push("pclmulqdq");
+    }
+
+    if cfg!(target_feature = "popcnt") {
+        res.push("popcnt");
+    }
+
+    res
+}
+
+fn detected() -> u8 {
+    let mut res = DETECTED.load(Ordering::Relaxed);
+
+    if res == 0 {
+        res = INIT | detect();
+        DETECTED.store(res, Ordering::Relaxed);
+    }
+
+    res
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+fn detect() -> u8 {
+    let mut res = 0;
+
+    if is_x86_feature_detected!("avx2") {
+        res |= AVX2;
+    }
+
+    if is_x86_feature_detected!("bmi2") {
+        res |= BMI2;
+    }
+
+    if is_x86_feature_detected!("ssse3") {
+        res |= SSSE3;
+    }
+
+    if is_x86_feature_detected!("pclmulqdq") {
+        res |= PCLMULQDQ;
+    }
+
+    res
+}
+
+// none of the vectorized methods can be used on other platforms
+#[cfg(not(any(target_arch = "x86", target_arch = "x86_64")))]
+fn detect() -> u8 {
+    0
+}
+
+#[inline]
+fn supports(features: u8) -> bool {
+    detected() & features == features
+}
+
+/// Check whether the running CPU supports AVX2. The result is cached after the first call.
+pub fn has_avx2() -> bool {
+    supports(AVX2)
+}
+
+/// Check whether the running CPU supports both AVX2 and BMI2. The result is cached after the first call.
+pub fn has_avx2_bmi2() -> bool {
+    supports(AVX2 | BMI2)
+}
+
+/// Encode `{A, T/U, C, G}` from the byte string into pairs of bits (`{00, 10, 01, 11}`) packed into 64-bit integers,
+/// by using the fastest method that the running CPU supports.
+///
+/// Falls back to `n_to_bits_lut` if AVX2 is not supported, or on platforms other than x86.
+pub fn encode(n: &[u8]) -> Vec<u64> {
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    if has_avx2() {
+        return unsafe {n_to_bits_movemask(n)};
+    }
+
+    n_to_bits_lut(n)
+}
+
+/// Decode pairs of bits from packed 64-bit integers to get a byte string of `{A, T, C, G}`, by using the fastest
+/// method that the running CPU supports.
+///
+/// Falls back to `bits_to_n_clmul` if AVX2 is not supported, and to `bits_to_n_lut` if neither AVX2 nor SSSE3 and
+/// PCLMULQDQ are supported, or on platforms other than x86.
+pub fn decode(bits: &[u64], len: usize) -> Vec<u8> {
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    {
+        if has_avx2() {
+            return unsafe {bits_to_n_shuffle(bits, len)};
+        }
+
+        if supports(SSSE3 | PCLMULQDQ) {
+            return unsafe {bits_to_n_clmul(bits, len)};
+        }
+    }
+
+    bits_to_n_lut(bits, len)
+}
+
+/// Encode each triplet of `{A, T/U, C, G, N}` from the byte string into 7 bits, then pack every 9 triplets into
+/// a single 64-bit integer, by using the fastest method that the running CPU supports.
+///
+/// Falls back to `n_to_bits2_lut` if AVX2 and BMI2 are not supported, or on platforms other than x86.
+pub fn encode2(n: &[u8]) -> Vec<u64> {
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    if has_avx2_bmi2() {
+        return unsafe {n_to_bits2_pext(n)};
+    }
+
+    n_to_bits2_lut(n)
+}
+
+/// Decode the 9 triplets of `{A, T/U, C, G, N}` that are packed into every 64-bit integer into a byte string, by
+/// using the fastest method that the running CPU supports.
+///
+/// Falls back to `bits_to_n2_lut` if AVX2 and BMI2 are not supported, or on platforms other than x86.
+pub fn decode2(bits: &[u64], len: usize) -> Vec<u8> {
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    if has_avx2_bmi2() {
+        return unsafe {bits_to_n2_pdep(bits, len)};
+    }
+
+    bits_to_n2_lut(bits, len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compiled_features() {
+        let features = compiled_features();
+        assert!(features.len() <= 9);
+        assert_eq!(features.contains(&"avx2"), cfg!(target_feature = "avx2"));
+    }
+
+    #[test]
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    fn test_has_avx2() {
+        assert_eq!(has_avx2(), is_x86_feature_detected!("avx2"));
+        assert_eq!(has_avx2_bmi2(), is_x86_feature_detected!("avx2") && is_x86_feature_detected!("bmi2"));
+    }
+
+    #[test]
+    fn test_encode() {
+        let n = b"ATCGATCGATCGATCGATCGATCGATCGATCGATCGA";
+        assert_eq!(encode(n), n_to_bits_lut(n));
+        assert_eq!(encode(b"ATCG"), vec![0b11011000]);
+        assert_eq!(encode(b""), Vec::<u64>::new());
+    }
+
+    #[test]
+    fn test_decode() {
+        let bits = n_to_bits_lut(b"ATCGATCGATCGATCGATCGATCGATCGATCGATCGA");
+        assert_eq!(decode(&bits, 37), bits_to_n_lut(&bits, 37));
+        assert_eq!(decode(&bits, 3), b"ATC".to_vec());
+        assert_eq!(decode(&encode(b""), 0), Vec::<u8>::new());
+        assert_eq!(decode(&bits, 0), Vec::<u8>::new());
+    }
+
+    #[test]
+    fn test_encode2() {
+        let n = b"ATCGNATCGNATCGNATCGNATCGNATCGNATCGN";
+        assert_eq!(encode2(n), n_to_bits2_lut(n));
+        assert_eq!(encode2(b""), Vec::<u64>::new());
+    }
+
+    #[test]
+    fn test_decode2() {
+        let n = b"ATCGNATCGNATCGNATCGNATCGNATCGNATCGN";
+        let bits = n_to_bits2_lut(n);
+        assert_eq!(decode2(&bits, n.len()), n.to_vec());
+        assert_eq!(decode2(&[], 0), Vec::<u8>::new());
+    }
+}
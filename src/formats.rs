@@ -0,0 +1,196 @@
+use std::io::{self, BufRead, BufReader, Read};
+
+use crate::ascii::composition;
+use crate::n_to_bits::n_to_bits_lut;
+
+// packs each FASTA record as soon as the header of the next record or the end of the file is reached
+struct FastaRecords {
+    res: Vec<(Vec<u64>, usize)>,
+    seq: Vec<u8>,
+    in_record: bool
+}
+
+impl FastaRecords {
+    fn new() -> Self {
+        FastaRecords{res: Vec::new(), seq: Vec::new(), in_record: false}
+    }
+
+    fn push_line(&mut self, line: &[u8]) {
+        let line = line.strip_suffix(b"\n").unwrap_or(line);
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+
+        if line.first() == Some(&b'>') {
+            if self.in_record {
+                self.res.push((n_to_bits_lut(&self.seq), self.seq.len()));
+                self.seq.clear();
+            }
+
+            self.in_record = true;
+        } else if self.in_record {
+            self.seq.extend_from_slice(line);
+        }
+    }
+
+    fn finish(mut self) -> Vec<(Vec<u64>, usize)> {
+        if self.in_record {
+            self.res.push((n_to_bits_lut(&self.seq), self.seq.len()));
+        }
+
+        self.res
+    }
+}
+
+/// Encode the sequence of each record in a FASTA file into pairs of bits packed into 64-bit integers, skipping the
+/// header lines and line breaks.
+///
+/// Returns the packed sequence and its length for each record.
+pub fn encode_fasta(fasta: &[u8]) -> Vec<(Vec<u64>, usize)> {
+    let mut records = FastaRecords::new();
+
+    for line in fasta.split(|&c| c == b'\n') {
+        records.push_line(line);
+    }
+
+    records.finish()
+}
+
+/// Detect the line wrap width of the sequence in a FASTA record, which is the length of the first line of the
+/// sequence, skipping the header line if there is one.
+///
+/// Returns `None` if the sequence is not wrapped, so it is at most one line.
+pub fn detect_wrap_width(record: &[u8]) -> Option<usize> {
+    let mut lines = record.split(|&c| c == b'\n');
+
+    if record.first() == Some(&b'>') {
+        lines.next();
+    }
+
+    let first = lines.next()?;
+    let first = first.strip_suffix(b"\r").unwrap_or(first);
+
+    // the sequence must continue after a line break
+    match lines.next() {
+        Some(line) if !line.is_empty() && line != b"\r" && !first.is_empty() => Some(first.len()),
+        _ => None
+    }
+}
+
+/// Get the GC content of each read in a FASTQ file, without encoding the reads.
+///
+/// Each record must be exactly four lines. The GC content is the fraction of `{A, T/U, C, G}` nucleotides that are
+/// `C` or `G`, so `N` and other bytes are not counted. Reads without any of those nucleotides have a GC content of
+/// zero.
+pub fn fastq_gc_report<R: Read>(reader: R) -> io::Result<Vec<f32>> {
+    let mut res = Vec::new();
+    let mut lines = BufReader::new(reader).split(b'\n');
+
+    while let Some(header) = lines.next() {
+        let header = header?;
+
+        if header.is_empty() || header == b"\r" {
+            continue;
+        }
+
+        if header.first() != Some(&b'@') {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "FASTQ record does not start with '@'"));
+        }
+
+        let seq = lines.next().ok_or_else(|| io::Error::new(io::ErrorKind::UnexpectedEof, "missing FASTQ sequence"))??;
+        let seq = seq.strip_suffix(b"\r").unwrap_or(&seq);
+
+        for _ in 0..2 {
+            lines.next().ok_or_else(|| io::Error::new(io::ErrorKind::UnexpectedEof, "truncated FASTQ record"))??;
+        }
+
+        let counts = composition(seq);
+        let total = counts.a + counts.c + counts.g + counts.t;
+        res.push(if total == 0 {0.0} else {((counts.c + counts.g) as f32) / (total as f32)});
+    }
+
+    Ok(res)
+}
+
+/// Decompress a gzip compressed FASTA file and encode the sequence of each record into pairs of bits packed into
+/// 64-bit integers, skipping the header lines and line breaks.
+///
+/// The file is decompressed and parsed one line at a time, so only the record being encoded is kept in memory
+/// as bytes.
+///
+/// Returns the packed sequence and its length for each record.
+#[cfg(feature = "flate2")]
+pub fn encode_fasta_gz<R: Read>(reader: R) -> io::Result<Vec<(Vec<u64>, usize)>> {
+    let mut reader = BufReader::new(flate2::read::MultiGzDecoder::new(reader));
+    let mut records = FastaRecords::new();
+    let mut line = Vec::new();
+
+    while reader.read_until(b'\n', &mut line)? > 0 {
+        records.push_line(&line);
+        line.clear();
+    }
+
+    Ok(records.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::n_to_bits::*;
+
+    #[test]
+    fn test_encode_fasta() {
+        let fasta = b">seq1 description\nATCGATCGATCGATCGATCG\nATCGATCGATCGATCGATCG\nAT\n>seq2\r\nGGCC\r\nTT\r\n>seq3\n";
+        let res = encode_fasta(fasta);
+        assert_eq!(res.len(), 3);
+        assert_eq!(bits_to_n_lut(&res[0].0, res[0].1), b"ATCGATCGATCGATCGATCGATCGATCGATCGATCGATCGAT".to_vec());
+        assert_eq!(res[1], (n_to_bits_lut(b"GGCCTT"), 6));
+        assert_eq!(res[2], (vec![], 0));
+        assert_eq!(encode_fasta(b""), vec![]);
+    }
+
+    #[test]
+    fn test_detect_wrap_width() {
+        let seq = b"ATCG".repeat(40);
+        let mut record = b">seq1 description\n".to_vec();
+
+        for line in seq.chunks(60) {
+            record.extend_from_slice(line);
+            record.push(b'\n');
+        }
+
+        assert_eq!(detect_wrap_width(&record), Some(60));
+        assert_eq!(detect_wrap_width(&record[18..]), Some(60));
+        assert_eq!(detect_wrap_width(b">seq1\r\nATCGAT\r\nAT\r\n"), Some(6));
+        assert_eq!(detect_wrap_width(b">seq1\nATCGATCG\n"), None);
+        assert_eq!(detect_wrap_width(b">seq1\nATCGATCG"), None);
+        assert_eq!(detect_wrap_width(b""), None);
+    }
+
+    #[test]
+    fn test_fastq_gc_report() {
+        let fastq = b"@read1\nATCGATCG\n+\nIIIIIIII\n@read2\r\nGGACNNNN\r\n+\r\nIIIIIIII\r\n@read3\n\n+\n\n@read4\nNN\n+\nII\n";
+        assert_eq!(fastq_gc_report(&fastq[..]).unwrap(), vec![0.5, 0.75, 0.0, 0.0]);
+        assert_eq!(fastq_gc_report(&b""[..]).unwrap(), vec![]);
+        assert!(fastq_gc_report(&b"@read1\nATCG\n+\n"[..]).is_err());
+        assert!(fastq_gc_report(&b">read1\nATCG\n+\nIIII\n"[..]).is_err());
+    }
+
+    #[cfg(feature = "flate2")]
+    #[test]
+    fn test_encode_fasta_gz() {
+        use std::io::Write;
+
+        let fasta = b">seq1\nATCGATCGATCGATCGATCG\nATCGATCGATCGATCGATCG\n>seq2\nGGCC\n";
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(fasta).unwrap();
+        let gz = encoder.finish().unwrap();
+
+        assert_eq!(encode_fasta_gz(&gz[..]).unwrap(), encode_fasta(fasta));
+
+        let fasta = b">seq1\r\nATCG\r\nAT\r\n>seq2\nGGCC";
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(fasta).unwrap();
+        let gz = encoder.finish().unwrap();
+
+        assert_eq!(encode_fasta_gz(&gz[..]).unwrap(), vec![(n_to_bits_lut(b"ATCGAT"), 6), (n_to_bits_lut(b"GGCC"), 4)]);
+    }
+}
@@ -0,0 +1,189 @@
+use crate::count::*;
+use crate::packed::*;
+
+/// Count the number of occurrences of the nucleotide with the two-bit encoding `base` in the range `[0, i)` of a
+/// packed sequence.
+pub fn rank_base(bits: &[u64], len: usize, base: u8, i: usize) -> usize {
+    ranged_base_counts(bits, len, 0, i)[base as usize]
+}
+
+/// Get the index of the `n`-th (starting from one) occurrence of the nucleotide with the two-bit encoding `base` in a
+/// packed sequence, or `None` if there are less than `n` occurrences.
+pub fn select_base(bits: &[u64], len: usize, base: u8, n: usize) -> Option<usize> {
+    if len > (bits.len() << 5) {
+        panic!("The length is greater than the number of nucleotides!");
+    }
+
+    if n == 0 {
+        return None;
+    }
+
+    let mut remaining = n;
+
+    for (i, &w) in bits[..words(len)].iter().enumerate() {
+        let mut matches = match_fields(w, base) & range_mask(0, len, i);
+        let count = matches.count_ones() as usize;
+
+        if count < remaining {
+            remaining -= count;
+            continue;
+        }
+
+        // clear the lowest set bits until the target is the lowest one
+        for _ in 1..remaining {
+            matches &= matches - 1;
+        }
+
+        return Some((i << 5) + (matches.trailing_zeros() >> 1) as usize);
+    }
+
+    None
+}
+
+/// Index of a packed sequence with the cumulative count of each nucleotide before every 64-bit integer, for constant
+/// time rank queries.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RankIndex {
+    bits: Vec<u64>,
+    len: usize,
+    counts: [Vec<usize>; 4]
+}
+
+impl RankIndex {
+    /// Count the number of occurrences of the nucleotide with the two-bit encoding `base` in the range `[0, i)`, like
+    /// `rank_base`.
+    pub fn rank(&self, base: u8, i: usize) -> usize {
+        if i > self.len {
+            panic!("The index is out of bounds!");
+        }
+
+        let word = i >> 5;
+        let before = self.counts[base as usize][word];
+
+        if i & 31 == 0 {
+            return before;
+        }
+
+        before + (match_fields(self.bits[word], base) & range_mask(0, i, word)).count_ones() as usize
+    }
+
+    /// Get the length of the indexed sequence.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Check whether the indexed sequence is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+/// Build a rank index of the first `len` nucleotides of a packed sequence.
+pub fn build_rank_index(bits: &[u64], len: usize) -> RankIndex {
+    if len > (bits.len() << 5) {
+        panic!("The length is greater than the number of nucleotides!");
+    }
+
+    let n = words(len);
+    let mut counts = [vec![0usize; n + 1], vec![0usize; n + 1], vec![0usize; n + 1], vec![0usize; n + 1]];
+
+    for i in 0..n {
+        let mask = range_mask(0, len, i);
+
+        for (base, count) in counts.iter_mut().enumerate() {
+            count[i + 1] = count[i] + (match_fields(bits[i], base as u8) & mask).count_ones() as usize;
+        }
+    }
+
+    RankIndex{bits: bits[..n].to_vec(), len, counts}
+}
+
+/// Get the suffix array of a short packed sequence, which is the start index of each suffix in sorted order.
+///
+/// The suffixes are compared with `cmp_bits`, so the nucleotides are ordered by their two-bit encodings
+/// (`A < C < T < G`). Every suffix is kept in memory while sorting, so this is only meant for short sequences.
+pub fn suffix_array(bits: &[u64], len: usize) -> Vec<u32> {
+    if len > (bits.len() << 5) {
+        panic!("The length is greater than the number of nucleotides!");
+    }
+
+    let suffixes = (0..len).map(|i| slice_bits(bits, i, len - i)).collect::<Vec<_>>();
+    let mut res = (0..len as u32).collect::<Vec<_>>();
+    res.sort_unstable_by(|&i, &j| {
+        let (i, j) = (i as usize, j as usize);
+        cmp_bits(&suffixes[i], len - i, &suffixes[j], len - j)
+    });
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::n_to_bits::*;
+
+    #[test]
+    fn test_rank_base() {
+        let n = b"ATCGGATCGATCGATCGATCGATCGATCGATCGATCG";
+        let bits = n_to_bits_lut(n);
+
+        for i in 0..=n.len() {
+            for (base, &c) in [b'A', b'C', b'T', b'G'].iter().enumerate() {
+                let expected = n[..i].iter().filter(|&&x| x == c).count();
+                assert_eq!(rank_base(&bits, n.len(), base as u8, i), expected);
+            }
+        }
+
+        assert_eq!(rank_base(&bits, n.len(), 0b11, 5), 2);
+    }
+
+    #[test]
+    fn test_build_rank_index() {
+        let n = b"ATCGGATCGATCGATCGATCGATCGATCGATCGATCGAAAAAAAAAAAAAAAAAAAAAAAAAAAAATTCG";
+        let bits = n_to_bits_lut(n);
+        let index = build_rank_index(&bits, n.len());
+        assert_eq!(index.len(), n.len());
+
+        for i in 0..=n.len() {
+            for base in 0..4 {
+                assert_eq!(index.rank(base, i), rank_base(&bits, n.len(), base, i));
+            }
+        }
+
+        // padding must not be counted as A
+        assert_eq!(build_rank_index(&bits, 40).rank(0b00, 40), rank_base(&bits, 40, 0b00, 40));
+        assert!(build_rank_index(&[], 0).is_empty());
+    }
+
+    #[test]
+    fn test_select_base() {
+        let n = b"ATCGGATCGATCGATCGATCGATCGATCGATCGATCGA";
+        let bits = n_to_bits_lut(n);
+        assert_eq!(select_base(&bits, n.len(), 0b11, 1), Some(3));
+        assert_eq!(select_base(&bits, n.len(), 0b11, 3), Some(8));
+        assert_eq!(select_base(&bits, n.len(), 0b11, 10), Some(36));
+        assert_eq!(select_base(&bits, n.len(), 0b11, 11), None);
+        assert_eq!(select_base(&bits, n.len(), 0b00, 0), None);
+
+        // padding must not be counted as A
+        assert_eq!(select_base(&bits, n.len(), 0b00, 11), None);
+
+        for k in 1..=9 {
+            let i = select_base(&bits, n.len(), 0b10, k).unwrap();
+            assert_eq!(rank_base(&bits, n.len(), 0b10, i + 1), k);
+            assert_eq!(n[i], b'T');
+        }
+    }
+
+    #[test]
+    fn test_suffix_array() {
+        let n = b"GATTACACATTAGGATTACAGATCGATCGGGTACCA";
+        // map the nucleotides to bytes in the same order as the two-bit encodings
+        let ranked = n.iter().map(|&c| match c {b'A' => 0, b'C' => 1, b'T' => 2, _ => 3}).collect::<Vec<u8>>();
+        let mut expected = (0..n.len() as u32).collect::<Vec<_>>();
+        expected.sort_by_key(|&i| &ranked[i as usize..]);
+
+        assert_eq!(suffix_array(&n_to_bits_lut(n), n.len()), expected);
+        assert_eq!(suffix_array(&n_to_bits_lut(b"AAA"), 3), vec![2, 1, 0]);
+        assert_eq!(suffix_array(&[], 0), vec![]);
+    }
+}
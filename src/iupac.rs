@@ -0,0 +1,104 @@
+use crate::packed::get_base;
+
+// each bit is a possible nucleotide, indexed by its two-bit encoding
+const A: u8 = 1 << 0b00;
+const C: u8 = 1 << 0b01;
+const T: u8 = 1 << 0b10;
+const G: u8 = 1 << 0b11;
+
+// non-ASCII bytes are unknown, so they must not alias the ASCII codes
+static BYTE_LUT: [u8; 256] = {
+    let mut lut = [0u8; 256];
+    let codes = [
+        (b'A', A), (b'C', C), (b'G', G), (b'T', T), (b'U', T),
+        (b'R', A | G), (b'Y', C | T), (b'S', G | C), (b'W', A | T), (b'K', G | T), (b'M', A | C),
+        (b'B', C | G | T), (b'D', A | G | T), (b'H', A | C | T), (b'V', A | C | G), (b'N', A | C | G | T)
+    ];
+    let mut i = 0;
+    while i < codes.len() {
+        lut[codes[i].0 as usize] = codes[i].1;
+        lut[(codes[i].0 | 0x20) as usize] = codes[i].1;
+        i += 1;
+    }
+    lut
+};
+
+/// Encode IUPAC nucleotide codes from the byte string into sets of possible nucleotides with 4 bits each, packed into
+/// 64-bit integers, by using a naive scalar method.
+///
+/// Bit `i` of each 4-bit set is set if the nucleotide with the two-bit encoding `i` (`{A = 00, T/U = 10, C = 01,
+/// G = 11}`) is possible, so `R` (A or G) is `1001`. Unknown bytes are encoded as empty sets.
+pub fn n_to_iupac_lut(n: &[u8]) -> Vec<u64> {
+    let mut res = vec![0u64; (n.len() >> 4) + if n.len() & 15 == 0 {0} else {1}];
+
+    for (i, &c) in n.iter().enumerate() {
+        res[i >> 4] |= (BYTE_LUT[c as usize] as u64) << ((i & 15) << 2);
+    }
+
+    res
+}
+
+/// Convert IUPAC nucleotide codes from the byte string into a probability distribution over the possible nucleotides
+/// at each position, where each possible nucleotide has the same probability.
+///
+/// The probabilities are indexed by the two-bit encoding of each nucleotide, so the order is `[A, C, T, G]`. `N` has
+/// a probability of 0.25 for each nucleotide, and unknown bytes have a probability of zero for every nucleotide.
+pub fn n_to_profile(n: &[u8]) -> Vec<[f32; 4]> {
+    n.iter().map(|&c| {
+        let set = BYTE_LUT[c as usize];
+        let p = 1.0 / (set.count_ones().max(1) as f32);
+        let mut res = [0.0f32; 4];
+
+        for (i, r) in res.iter_mut().enumerate() {
+            if (set >> i) & 1 == 1 {
+                *r = p;
+            }
+        }
+
+        res
+    }).collect()
+}
+
+/// Count the number of positions where the nucleotide in the two-bit packed query is not one of the possible
+/// nucleotides in the reference, which is encoded by `n_to_iupac_lut`.
+pub fn hamming_vs_iupac(query_2bit: &[u64], reference_4bit: &[u64], len: usize) -> usize {
+    if len > (query_2bit.len() << 5) || len > (reference_4bit.len() << 4) {
+        panic!("The length is greater than the number of nucleotides!");
+    }
+
+    (0..len)
+        .filter(|&i| {
+            let set = (reference_4bit[i >> 4] >> ((i & 15) << 2)) & 0b1111;
+            (set >> get_base(query_2bit, i)) & 1 == 0
+        })
+        .count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::n_to_bits::*;
+
+    #[test]
+    fn test_n_to_iupac_lut() {
+        assert_eq!(n_to_iupac_lut(b"ACGTRn"), vec![0b111110010100100000100001]);
+        assert_eq!(n_to_iupac_lut(b"ATCGATCGATCGATCGA").len(), 2);
+        assert_eq!(n_to_iupac_lut(b"\xc1\xd2\x80\xffA"), vec![0b00010000000000000000]);
+    }
+
+    #[test]
+    fn test_n_to_profile() {
+        assert_eq!(n_to_profile(b"ARN"), vec![[1.0, 0.0, 0.0, 0.0], [0.5, 0.0, 0.0, 0.5], [0.25, 0.25, 0.25, 0.25]]);
+        assert_eq!(n_to_profile(b"uB-"), vec![[0.0, 0.0, 1.0, 0.0], [0.0, 1.0 / 3.0, 1.0 / 3.0, 1.0 / 3.0], [0.0; 4]]);
+        assert!(n_to_profile(b"").is_empty());
+        assert_eq!(n_to_profile(b"\xc1\xce"), vec![[0.0; 4], [0.0; 4]]);
+    }
+
+    #[test]
+    fn test_hamming_vs_iupac() {
+        let reference = n_to_iupac_lut(b"ATCGRYNATCGATCGATCGATCGATCGATCGATCGA");
+        assert_eq!(hamming_vs_iupac(&n_to_bits_lut(b"ATCGACGATCGATCGATCGATCGATCGATCGATCGA"), &reference, 36), 0);
+        assert_eq!(hamming_vs_iupac(&n_to_bits_lut(b"ATCGGTTATCGATCGATCGATCGATCGATCGATCGA"), &reference, 36), 0);
+        assert_eq!(hamming_vs_iupac(&n_to_bits_lut(b"ATCGCATATCGATCGATCGATCGATCGATCGATCGG"), &reference, 36), 3);
+    }
+}
@@ -0,0 +1,609 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::n_to_bits::{bits_to_n_lut, n_to_bits_lut};
+use crate::packed::*;
+
+/// Iterator over all overlapping k-mers of a packed sequence, where each k-mer is packed into the low `2 * k` bits of
+/// a 64-bit integer with the same encoding as the sequence.
+pub struct KmerIter<'a> {
+    bits: &'a [u64],
+    len: usize,
+    k: usize,
+    mask: u64,
+    i: usize
+}
+
+impl<'a> KmerIter<'a> {
+    /// Create an iterator over the k-mers of the first `len` nucleotides in `bits`.
+    ///
+    /// Panics if `k` is zero or greater than 32.
+    pub fn new(bits: &'a [u64], len: usize, k: usize) -> Self {
+        if len > (bits.len() << 5) {
+            panic!("The length is greater than the number of nucleotides!");
+        }
+
+        if k == 0 || k > 32 {
+            panic!("The k-mer length must be between 1 and 32!");
+        }
+
+        KmerIter{bits, len, k, mask: kmer_mask(k), i: 0}
+    }
+}
+
+impl<'a> Iterator for KmerIter<'a> {
+    type Item = u64;
+
+    #[inline]
+    fn next(&mut self) -> Option<u64> {
+        if self.i + self.k > self.len {
+            return None;
+        }
+
+        let kmer = extract_kmer(self.bits, self.i, self.k);
+        self.i += 1;
+        Some(kmer & self.mask)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let n = (self.len + 1).saturating_sub(self.i + self.k);
+        (n, Some(n))
+    }
+}
+
+impl<'a> ExactSizeIterator for KmerIter<'a> {}
+
+/// Iterator over overlapping tiles of a packed sequence, where each tile is a packed subsequence and its length.
+///
+/// Tiles of length `tile` start every `step` nucleotides. If the last full tile does not reach the end of the
+/// sequence, then a shorter tile that starts at the next step and ends at the end of the sequence is also yielded.
+pub struct TileIter<'a> {
+    bits: &'a [u64],
+    len: usize,
+    tile: usize,
+    step: usize,
+    i: usize
+}
+
+impl<'a> TileIter<'a> {
+    /// Create an iterator over the tiles of the first `len` nucleotides in `bits`.
+    ///
+    /// Panics if `tile` or `step` is zero.
+    pub fn new(bits: &'a [u64], len: usize, tile: usize, step: usize) -> Self {
+        if len > (bits.len() << 5) {
+            panic!("The length is greater than the number of nucleotides!");
+        }
+
+        if tile == 0 || step == 0 {
+            panic!("The tile size and step must be greater than zero!");
+        }
+
+        TileIter{bits, len, tile, step, i: 0}
+    }
+}
+
+impl<'a> Iterator for TileIter<'a> {
+    type Item = (Vec<u64>, usize);
+
+    fn next(&mut self) -> Option<(Vec<u64>, usize)> {
+        if self.i >= self.len {
+            return None;
+        }
+
+        // the end of the previous tile, if there was one
+        if self.i > 0 && self.i - self.step + self.tile >= self.len {
+            return None;
+        }
+
+        let len = self.tile.min(self.len - self.i);
+        let res = slice_bits(self.bits, self.i, len);
+        self.i += self.step;
+        Some((res, len))
+    }
+}
+
+/// Mask of the low `2 * k` bits.
+#[inline]
+pub(crate) fn kmer_mask(k: usize) -> u64 {
+    if k >= 32 {!0} else {(1u64 << (k << 1)) - 1}
+}
+
+// the bits above the k-mer are not masked out
+#[inline]
+fn extract_kmer(bits: &[u64], pos: usize, k: usize) -> u64 {
+    let offset = pos >> 5;
+    let shift = (pos & 31) << 1;
+    let mut kmer = bits[offset] >> shift;
+
+    // the k-mer straddles two words
+    if shift + (k << 1) > 64 {
+        kmer |= bits[offset + 1] << (64 - shift);
+    }
+
+    kmer
+}
+
+/// Get the packed k-mer that starts at index `pos` of a packed sequence.
+///
+/// Panics if `k` is zero or greater than 32, or if the k-mer does not fit in the sequence.
+pub fn kmer_at(bits: &[u64], len: usize, pos: usize, k: usize) -> u64 {
+    if len > (bits.len() << 5) {
+        panic!("The length is greater than the number of nucleotides!");
+    }
+
+    if k == 0 || k > 32 {
+        panic!("The k-mer length must be between 1 and 32!");
+    }
+
+    if pos + k > len {
+        panic!("The k-mer is out of bounds!");
+    }
+
+    extract_kmer(bits, pos, k) & kmer_mask(k)
+}
+
+/// Get the packed spaced seed that starts at index `pos` of a packed sequence, which contains only the nucleotides
+/// at the offsets where `mask` is true, packed contiguously in order.
+///
+/// Panics if `mask` selects zero or more than 32 nucleotides, or if the span of `mask` does not fit in the sequence.
+pub fn spaced_kmer_at(bits: &[u64], len: usize, pos: usize, mask: &[bool]) -> u64 {
+    if len > (bits.len() << 5) {
+        panic!("The length is greater than the number of nucleotides!");
+    }
+
+    let k = mask.iter().filter(|&&m| m).count();
+
+    if k == 0 || k > 32 {
+        panic!("The k-mer length must be between 1 and 32!");
+    }
+
+    if pos + mask.len() > len {
+        panic!("The k-mer is out of bounds!");
+    }
+
+    let mut res = 0u64;
+
+    for (j, i) in (0..mask.len()).filter(|&i| mask[i]).enumerate() {
+        res |= (get_base(bits, pos + i) as u64) << (j << 1);
+    }
+
+    res
+}
+
+/// Get the reverse complement of a packed k-mer.
+#[inline]
+pub fn revcomp_kmer(kmer: u64, k: usize) -> u64 {
+    reverse_fields(kmer ^ HI_BITS) >> (64 - (k << 1))
+}
+
+/// Get the canonical form of a packed k-mer, which is the smaller of the k-mer and its reverse complement when
+/// compared as integers.
+#[inline]
+pub fn canonical_kmer(kmer: u64, k: usize) -> u64 {
+    kmer.min(revcomp_kmer(kmer, k))
+}
+
+/// Get the canonical form of a packed k-mer, along with whether the canonical form is the reverse complement.
+///
+/// If the k-mer is its own reverse complement, then the forward strand is used.
+#[inline]
+pub fn canonical_kmer_with_strand(kmer: u64, k: usize) -> (u64, bool) {
+    let rc = revcomp_kmer(kmer, k);
+    if rc < kmer {(rc, true)} else {(kmer, false)}
+}
+
+/// Iterate over the canonical forms of all overlapping k-mers of a packed sequence.
+pub fn canonical_kmers(bits: &[u64], len: usize, k: usize) -> impl Iterator<Item = u64> + '_ {
+    KmerIter::new(bits, len, k).map(move |kmer| canonical_kmer(kmer, k))
+}
+
+/// Get all overlapping k-mers of a packed sequence, where each k-mer is packed into the low `2 * k` bits of a 64-bit
+/// integer with the same encoding as the sequence.
+///
+/// Panics if `k` is zero, greater than 32, or greater than `len`. This is unlike `KmerIter`, which yields no k-mers
+/// if `k` is greater than `len`.
+pub fn kmers(bits: &[u64], len: usize, k: usize) -> Vec<u64> {
+    if k > len {
+        panic!("The k-mer length is greater than the length!");
+    }
+
+    KmerIter::new(bits, len, k).collect()
+}
+
+/// Count the number of (possibly overlapping) occurrences of the packed k-mer `query` in a packed sequence.
+pub fn count_kmer(bits: &[u64], len: usize, query: u64, k: usize) -> usize {
+    let query = query & kmer_mask(k);
+    KmerIter::new(bits, len, k).filter(|&kmer| kmer == query).count()
+}
+
+/// Count the number of occurrences of each canonical k-mer in a packed sequence.
+pub fn kmer_spectrum(bits: &[u64], len: usize, k: usize) -> HashMap<u64, u32> {
+    let mut res = HashMap::new();
+
+    for kmer in canonical_kmers(bits, len, k) {
+        *res.entry(kmer).or_insert(0) += 1;
+    }
+
+    res
+}
+
+/// Convert k-mer counts into a histogram, where index `i` is the number of distinct k-mers that occur `i` times.
+pub fn spectrum_histogram(spectrum: &HashMap<u64, u32>) -> Vec<u32> {
+    let max = spectrum.values().copied().max().unwrap_or(0) as usize;
+    let mut res = vec![0u32; max + 1];
+
+    for &count in spectrum.values() {
+        res[count as usize] += 1;
+    }
+
+    res
+}
+
+/// Count the number of distinct k-mers in a packed sequence.
+pub fn distinct_kmers(bits: &[u64], len: usize, k: usize) -> usize {
+    KmerIter::new(bits, len, k).collect::<HashSet<_>>().len()
+}
+
+/// Get a bitset where bit `j` is set if the canonical k-mer with the value `j` occurs in a packed sequence.
+///
+/// The bitset has `4^k` bits, so `k` must be at most 13.
+pub fn kmer_presence_set(bits: &[u64], len: usize, k: usize) -> Vec<u64> {
+    if k == 0 || k > 13 {
+        panic!("The k-mer length must be between 1 and 13!");
+    }
+
+    let mut res = vec![0u64; ((1usize << (k << 1)) + 63) >> 6];
+
+    for kmer in canonical_kmers(bits, len, k) {
+        res[(kmer >> 6) as usize] |= 1 << (kmer & 63);
+    }
+
+    res
+}
+
+/// Get the ratio of the number of distinct k-mers to the number of k-mers in a packed sequence.
+///
+/// Repetitive sequences have a low ratio, while random sequences have a ratio close to one. Sequences that are shorter
+/// than `k` have a ratio of zero.
+pub fn linguistic_complexity(bits: &[u64], len: usize, k: usize) -> f32 {
+    if len < k {
+        return 0.0;
+    }
+
+    (distinct_kmers(bits, len, k) as f32) / ((len - k + 1) as f32)
+}
+
+/// Estimate the entropy rate (in bits per nucleotide) of a packed sequence, which is the conditional entropy of each
+/// nucleotide given the previous `k - 1` nucleotides, from the counts of the k-mers and their `(k - 1)`-mer prefixes.
+///
+/// Periodic sequences have an entropy rate close to zero, while random sequences have an entropy rate close to 2
+/// bits. Sequences that are shorter than `k` have an entropy rate of zero.
+pub fn entropy_rate(bits: &[u64], len: usize, k: usize) -> f32 {
+    // create the iterator first so the k-mer length is checked
+    let iter = KmerIter::new(bits, len, k);
+    let prefix_mask = kmer_mask(k - 1);
+    let mut kmers = HashMap::new();
+    let mut prefixes = HashMap::new();
+
+    for kmer in iter {
+        *kmers.entry(kmer).or_insert(0u32) += 1;
+        *prefixes.entry(kmer & prefix_mask).or_insert(0u32) += 1;
+    }
+
+    let total = kmers.values().sum::<u32>() as f32;
+
+    kmers.iter().map(|(&kmer, &count)| {
+        let p = (count as f32) / (prefixes[&(kmer & prefix_mask)] as f32);
+        -(count as f32) / total * p.log2()
+    }).sum()
+}
+
+/// Get the de Bruijn graph edge for each k-mer of a packed sequence, where each edge is the `(k - 1)`-mer prefix and
+/// the `(k - 1)`-mer suffix of the k-mer, in the same packed format.
+pub fn de_bruijn_edges(bits: &[u64], len: usize, k: usize) -> Vec<(u64, u64)> {
+    if k < 2 {
+        panic!("The k-mer length must be at least 2!");
+    }
+
+    let prefix_mask = kmer_mask(k - 1);
+    // the first nucleotide is in the lowest bits
+    KmerIter::new(bits, len, k).map(|kmer| (kmer & prefix_mask, kmer >> 2)).collect()
+}
+
+/// Get every overlapping k-mer of a byte string of `{A, T/U, C, G}`, decoded as a byte string.
+///
+/// This is convenient for debugging, but it is much slower than using `KmerIter` directly. Panics if `k` is zero or
+/// greater than 32, like `KmerIter`.
+pub fn kmers_ascii(n: &[u8], k: usize) -> Vec<Vec<u8>> {
+    let bits = n_to_bits_lut(n);
+    KmerIter::new(&bits, n.len(), k).map(|kmer| bits_to_n_lut(&[kmer], k)).collect()
+}
+
+/// Get the minimizers of a packed sequence, which are the smallest canonical k-mers in each window of `w` consecutive
+/// k-mers.
+///
+/// Returns the start index and the canonical k-mer of each minimizer, in order. Ties are broken by picking the leftmost
+/// k-mer, and a k-mer that is the minimizer of multiple consecutive windows is only included once.
+pub fn minimizers(bits: &[u64], len: usize, k: usize, w: usize) -> Vec<(usize, u64)> {
+    if w == 0 {
+        panic!("The window size must be greater than zero!");
+    }
+
+    let kmers = canonical_kmers(bits, len, k).collect::<Vec<_>>();
+    let mut res: Vec<(usize, u64)> = Vec::new();
+
+    for start in 0..(kmers.len() + 1).saturating_sub(w) {
+        let (i, &kmer) = kmers[start..start + w].iter().enumerate().min_by_key(|&(_, &kmer)| kmer).unwrap();
+
+        if res.last().map(|&(j, _)| j) != Some(start + i) {
+            res.push((start + i, kmer));
+        }
+    }
+
+    res
+}
+
+/// Get the Jaccard similarity between the sets of minimizers of two packed sequences.
+///
+/// This is a fast estimate of the similarity between the sets of k-mers. Two sequences without any minimizers have a
+/// similarity of one.
+pub fn minimizer_jaccard(a_bits: &[u64], a_len: usize, b_bits: &[u64], b_len: usize, k: usize, w: usize) -> f32 {
+    let a = minimizers(a_bits, a_len, k, w).into_iter().map(|(_, kmer)| kmer).collect::<HashSet<_>>();
+    let b = minimizers(b_bits, b_len, k, w).into_iter().map(|(_, kmer)| kmer).collect::<HashSet<_>>();
+    let union = a.union(&b).count();
+
+    if union == 0 {
+        return 1.0;
+    }
+
+    (a.intersection(&b).count() as f32) / (union as f32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kmer_iter() {
+        let n = b"ATCGATCGATCGATCGATCGATCGATCGATCGATCGGG";
+        let bits = n_to_bits_lut(n);
+        let kmers = KmerIter::new(&bits, n.len(), 5).collect::<Vec<_>>();
+        assert_eq!(kmers.len(), n.len() - 4);
+
+        for (i, &kmer) in kmers.iter().enumerate() {
+            assert_eq!(kmer, n_to_bits_lut(&n[i..i + 5])[0]);
+        }
+
+        assert_eq!(KmerIter::new(&bits, n.len(), 32).count(), 7);
+        assert_eq!(KmerIter::new(&bits, 3, 4).count(), 0);
+    }
+
+    #[test]
+    fn test_kmer_at() {
+        let n = b"ATCGATCGATCGATCGATCGATCGATCGATCGGGCCATCG";
+        let bits = n_to_bits_lut(n);
+        assert_eq!(kmer_at(&bits, n.len(), 1, 5), n_to_bits_lut(b"TCGAT")[0]);
+        assert_eq!(kmer_at(&bits, n.len(), 28, 8), n_to_bits_lut(b"ATCGGGCC")[0]);
+        assert_eq!(kmer_at(&bits, n.len(), 8, 32), n_to_bits_lut(&n[8..])[0]);
+        assert_eq!(kmer_at(&bits, n.len(), 39, 1), n_to_bits_lut(b"G")[0]);
+
+        for (i, kmer) in KmerIter::new(&bits, n.len(), 7).enumerate() {
+            assert_eq!(kmer_at(&bits, n.len(), i, 7), kmer);
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_kmer_at_out_of_bounds() {
+        let bits = n_to_bits_lut(b"ATCGATCG");
+        kmer_at(&bits, 8, 4, 5);
+    }
+
+    #[test]
+    fn test_spaced_kmer_at() {
+        let n = b"ATCGATCGATCGATCGATCGATCGATCGATCGGGCCATCG";
+        let bits = n_to_bits_lut(n);
+        let mask = [true, true, false, true, true, false, true];
+        assert_eq!(spaced_kmer_at(&bits, n.len(), 0, &mask), n_to_bits_lut(b"ATGAC")[0]);
+        assert_eq!(spaced_kmer_at(&bits, n.len(), 29, &mask), n_to_bits_lut(b"TCGGC")[0]);
+        assert_eq!(spaced_kmer_at(&bits, n.len(), 3, &[true; 5]), kmer_at(&bits, n.len(), 3, 5));
+    }
+
+    #[test]
+    fn test_tile_iter() {
+        let n = random_nucleotides(100, 2);
+        let bits = n_to_bits_lut(&n);
+
+        let tiles = TileIter::new(&bits, 100, 40, 20).collect::<Vec<_>>();
+        assert_eq!(tiles.len(), 4);
+
+        for (i, (tile, len)) in tiles.iter().enumerate() {
+            assert_eq!(*len, 40);
+            assert_eq!(bits_to_n_lut(tile, *len), n[(i * 20)..(i * 20 + 40)].to_vec());
+        }
+
+        let tiles = TileIter::new(&bits, 90, 40, 20).collect::<Vec<_>>();
+        assert_eq!(tiles.iter().map(|t| t.1).collect::<Vec<_>>(), vec![40, 40, 40, 30]);
+        assert_eq!(bits_to_n_lut(&tiles[3].0, 30), n[60..90].to_vec());
+
+        let tiles = TileIter::new(&bits, 10, 40, 20).collect::<Vec<_>>();
+        assert_eq!(tiles, vec![(n_to_bits_lut(&n[..10]), 10)]);
+        assert_eq!(TileIter::new(&bits, 0, 40, 20).count(), 0);
+    }
+
+    #[test]
+    fn test_kmers() {
+        let n = random_nucleotides(100, 7);
+        let bits = n_to_bits_lut(&n);
+
+        for &k in &[1, 5, 31, 32] {
+            let res = kmers(&bits, n.len(), k);
+            assert_eq!(res.len(), n.len() - k + 1);
+
+            for (i, &kmer) in res.iter().enumerate() {
+                assert_eq!(bits_to_n_lut(&[kmer], k), n[i..i + k].to_vec());
+            }
+        }
+
+        assert_eq!(kmers(&bits, 4, 4), vec![n_to_bits_lut(&n[..4])[0]]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_kmers_too_long() {
+        kmers(&n_to_bits_lut(b"ATCG"), 4, 5);
+    }
+
+    #[test]
+    fn test_count_kmer() {
+        let bits = n_to_bits_lut(b"AAAATCGAAATCGA");
+        assert_eq!(count_kmer(&bits, 14, n_to_bits_lut(b"AA")[0], 2), 5);
+        assert_eq!(count_kmer(&bits, 14, n_to_bits_lut(b"TCGA")[0], 4), 2);
+        assert_eq!(count_kmer(&bits, 14, n_to_bits_lut(b"GG")[0], 2), 0);
+    }
+
+    #[test]
+    fn test_revcomp_kmer() {
+        assert_eq!(revcomp_kmer(n_to_bits_lut(b"AACG")[0], 4), n_to_bits_lut(b"CGTT")[0]);
+        assert_eq!(revcomp_kmer(n_to_bits_lut(b"G")[0], 1), n_to_bits_lut(b"C")[0]);
+        let n = b"ATCGGATCGATCGATCGATCGATCGATCGAAC";
+        assert_eq!(revcomp_kmer(n_to_bits_lut(n)[0], 32), n_to_bits_lut(b"GTTCGATCGATCGATCGATCGATCGATCCGAT")[0]);
+    }
+
+    #[test]
+    fn test_canonical_kmer() {
+        // the integer values are compared, so the first nucleotide is the least significant
+        assert_eq!(canonical_kmer(n_to_bits_lut(b"AACG")[0], 4), n_to_bits_lut(b"CGTT")[0]);
+        assert_eq!(canonical_kmer(n_to_bits_lut(b"CGTT")[0], 4), n_to_bits_lut(b"CGTT")[0]);
+        assert_eq!(canonical_kmer(n_to_bits_lut(b"AAC")[0], 3), n_to_bits_lut(b"AAC")[0]);
+    }
+
+    #[test]
+    fn test_canonical_kmer_with_strand() {
+        let aac = n_to_bits_lut(b"AAC")[0];
+        let gtt = n_to_bits_lut(b"GTT")[0];
+        assert_eq!(canonical_kmer_with_strand(aac, 3), (aac, false));
+        assert_eq!(canonical_kmer_with_strand(gtt, 3), (aac, true));
+
+        let palindrome = n_to_bits_lut(b"ACGT")[0];
+        assert_eq!(canonical_kmer_with_strand(palindrome, 4), (palindrome, false));
+    }
+
+    #[test]
+    fn test_kmer_spectrum() {
+        // AAC occurs twice and its reverse complement GTT occurs once
+        let bits = n_to_bits_lut(b"AACTTAACGTT");
+        let spectrum = kmer_spectrum(&bits, 11, 3);
+        assert_eq!(spectrum[&n_to_bits_lut(b"AAC")[0]], 3);
+        assert_eq!(spectrum.values().sum::<u32>(), 9);
+
+        let hist = spectrum_histogram(&spectrum);
+        assert_eq!(hist[3], 1);
+        assert_eq!(hist.iter().enumerate().map(|(i, &c)| i as u32 * c).sum::<u32>(), 9);
+    }
+
+    fn random_nucleotides(len: usize, seed: u64) -> Vec<u8> {
+        let mut state = seed;
+        (0..len).map(|_| {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            b"ATCG"[(state >> 62) as usize]
+        }).collect()
+    }
+
+    #[test]
+    fn test_distinct_kmers() {
+        let bits = n_to_bits_lut(b"ATATATATAT");
+        assert_eq!(distinct_kmers(&bits, 10, 3), 2);
+        assert_eq!(distinct_kmers(&bits, 10, 1), 2);
+        let bits = n_to_bits_lut(b"ATCGGA");
+        assert_eq!(distinct_kmers(&bits, 6, 2), 5);
+    }
+
+    #[test]
+    fn test_kmer_presence_set() {
+        let bits = n_to_bits_lut(b"ATCGGA");
+        let set = kmer_presence_set(&bits, 6, 3);
+        assert_eq!(set.len(), 1);
+
+        let present = |kmer: &[u8]| {
+            let kmer = canonical_kmer(n_to_bits_lut(kmer)[0], 3);
+            (set[(kmer >> 6) as usize] >> (kmer & 63)) & 1 == 1
+        };
+        assert!(present(b"ATC"));
+        assert!(present(b"GAT"));
+        assert!(present(b"CGA"));
+        assert!(present(b"GGA"));
+        assert!(!present(b"AAA"));
+        assert!(!present(b"GCG"));
+        assert_eq!(set.iter().map(|w| w.count_ones()).sum::<u32>(), 4);
+
+        assert_eq!(kmer_presence_set(&bits, 6, 7), vec![0u64; 256]);
+    }
+
+    #[test]
+    fn test_linguistic_complexity() {
+        let n = b"AT".repeat(50);
+        assert!(linguistic_complexity(&n_to_bits_lut(&n), n.len(), 8) < 0.05);
+
+        let n = random_nucleotides(500, 1);
+        assert!(linguistic_complexity(&n_to_bits_lut(&n), n.len(), 8) > 0.95);
+
+        assert_eq!(linguistic_complexity(&n_to_bits_lut(b"ATC"), 3, 4), 0.0);
+    }
+
+    #[test]
+    fn test_entropy_rate() {
+        let n = b"ATCGGATCGGATCGGATCGGATCGGATCGGATCGGATCGG";
+        assert!(entropy_rate(&n_to_bits_lut(n), n.len(), 4).abs() < 1e-6);
+        assert!(entropy_rate(&n_to_bits_lut(n), n.len(), 1) > 1.5);
+
+        let n = random_nucleotides(100000, 1);
+        let rate = entropy_rate(&n_to_bits_lut(&n), n.len(), 3);
+        assert!(rate > 1.95 && rate <= 2.0);
+
+        assert_eq!(entropy_rate(&n_to_bits_lut(b"ATCG"), 4, 5), 0.0);
+    }
+
+    #[test]
+    fn test_de_bruijn_edges() {
+        let bits = n_to_bits_lut(b"ATCGA");
+        let kmer = |n: &[u8]| n_to_bits_lut(n)[0];
+        assert_eq!(de_bruijn_edges(&bits, 5, 3),
+                vec![(kmer(b"AT"), kmer(b"TC")), (kmer(b"TC"), kmer(b"CG")), (kmer(b"CG"), kmer(b"GA"))]);
+        assert_eq!(de_bruijn_edges(&bits, 2, 3), vec![]);
+    }
+
+    #[test]
+    fn test_kmers_ascii() {
+        assert_eq!(kmers_ascii(b"ATCG", 2), vec![b"AT".to_vec(), b"TC".to_vec(), b"CG".to_vec()]);
+        assert_eq!(kmers_ascii(b"ATCG", 4), vec![b"ATCG".to_vec()]);
+        assert_eq!(kmers_ascii(b"ATCG", 5), Vec::<Vec<u8>>::new());
+        assert_eq!(kmers_ascii(b"ATCGATCGATCGATCGATCGATCGATCGATCGA", 32)[1], b"TCGATCGATCGATCGATCGATCGATCGATCGA".to_vec());
+    }
+
+    #[test]
+    fn test_minimizers() {
+        let n = b"GGTACGTT";
+        let bits = n_to_bits_lut(n);
+        let kmer = |n: &[u8]| canonical_kmer(n_to_bits_lut(n)[0], 3);
+        assert_eq!(minimizers(&bits, n.len(), 3, 1).len(), 6);
+        assert_eq!(minimizers(&bits, n.len(), 3, 6), vec![(1, kmer(b"GTA"))]);
+        assert_eq!(minimizers(&bits, n.len(), 3, 7), vec![]);
+
+        for (i, m) in minimizers(&bits, n.len(), 3, 3) {
+            assert_eq!(m, kmer(&n[i..i + 3]));
+        }
+    }
+
+    #[test]
+    fn test_minimizer_jaccard() {
+        let a = random_nucleotides(1000, 1);
+        let b = random_nucleotides(1000, 2);
+        let (a_bits, b_bits) = (n_to_bits_lut(&a), n_to_bits_lut(&b));
+        assert_eq!(minimizer_jaccard(&a_bits, a.len(), &a_bits, a.len(), 15, 10), 1.0);
+        assert!(minimizer_jaccard(&a_bits, a.len(), &b_bits, b.len(), 15, 10) < 0.1);
+
+        // most of the minimizers are shared with a prefix
+        assert!(minimizer_jaccard(&a_bits, a.len(), &a_bits, 900, 15, 10) > 0.8);
+        assert_eq!(minimizer_jaccard(&[], 0, &[], 0, 15, 10), 1.0);
+    }
+}
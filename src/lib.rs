@@ -1,2 +1,21 @@
 pub mod n_to_bits;
 pub mod n_to_bits2;
+pub mod n_to_bits4;
+pub mod iupac;
+pub mod packed;
+pub mod batch;
+pub mod compact;
+pub mod count;
+pub mod index;
+pub mod kmer;
+pub mod seed;
+pub mod align;
+pub mod repeat;
+pub mod ascii;
+pub mod features;
+pub mod stream;
+pub mod formats;
+pub mod serial;
+
+#[cfg(feature = "bio")]
+pub mod records;
@@ -4,6 +4,14 @@ use std::arch::x86::*;
 use std::arch::x86_64::*;
 
 use std::alloc;
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::error::Error;
+use std::fmt;
+
+use crate::ascii::validate_dna;
+use crate::features::{decode, encode, has_avx2};
+use crate::packed::{cmp_bits, complement_bits, get_base, revcomp_bits};
 
 static BYTE_LUT: [u8; 128] = {
     let mut lut = [0u8; 128];
@@ -20,7 +28,7 @@ static BYTE_LUT: [u8; 128] = {
     lut
 };
 
-static BITS_LUT: [u8; 4] = {
+pub(crate) static BITS_LUT: [u8; 4] = {
     let mut lut = [0u8; 4];
     lut[0b00] = b'A';
     lut[0b10] = b'T';
@@ -33,6 +41,94 @@ static BITS_LUT: [u8; 4] = {
 /// by using a naive scalar method.
 pub fn n_to_bits_lut(n: &[u8]) -> Vec<u64> {
     let mut res = vec![0u64; (n.len() >> 5) + if n.len() & 31 == 0 {0} else {1}];
+    n_to_bits_lut_kernel(n, &mut res);
+    res
+}
+
+/// Error for a byte that is not a nucleotide that can be encoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvalidNucleotide {
+    /// Index of the byte in the byte string.
+    pub index: usize,
+    /// The byte that cannot be encoded.
+    pub byte: u8
+}
+
+impl fmt::Display for InvalidNucleotide {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid nucleotide {:?} at index {}", self.byte as char, self.index)?;
+
+        if self.byte | 0x20 == b'n' {
+            write!(f, " (use the n_to_bits2 module to encode N)")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Error for InvalidNucleotide {}
+
+/// Encode `{A, T/U, C, G}` from the byte string into pairs of bits (`{00, 10, 01, 11}`) packed into 64-bit integers,
+/// after checking that every byte is one of `{A, T/U, C, G}` (in either case).
+///
+/// The bytes are checked and encoded with the fastest methods that the running CPU supports.
+pub fn n_to_bits_checked(n: &[u8]) -> Result<Vec<u64>, InvalidNucleotide> {
+    match validate_dna(n) {
+        Some(index) => Err(InvalidNucleotide{index, byte: n[index]}),
+        None => Ok(encode(n))
+    }
+}
+
+/// Pack two-bit encodings (`{A: 0, T/U: 2, C: 1, G: 3}`) that are stored one per byte into 64-bit integers, without
+/// going through ASCII.
+///
+/// Each code must be less than 4.
+pub fn codes_to_bits(codes: &[u8]) -> Vec<u64> {
+    let mut res = vec![0u64; (codes.len() >> 5) + if codes.len() & 31 == 0 {0} else {1}];
+
+    for (i, &code) in codes.iter().enumerate() {
+        debug_assert!(code < 4, "The code is not a two-bit encoding!");
+        res[i >> 5] |= ((code & 0b11) as u64) << ((i & 31) << 1);
+    }
+
+    res
+}
+
+/// Encode `{A, T/U, C, G}` from the byte string into pairs of bits (`{00, 10, 01, 11}`) packed into 64-bit integers,
+/// by using a naive scalar method, storing the result in `out`.
+///
+/// The contents of `out` are replaced, and its allocation is reused if it is large enough.
+pub fn n_to_bits_lut_reuse(n: &[u8], out: &mut Vec<u64>) {
+    out.clear();
+    out.resize((n.len() >> 5) + if n.len() & 31 == 0 {0} else {1}, 0);
+    n_to_bits_lut_kernel(n, out);
+}
+
+/// Encode `{A, T/U, C, G}` from the byte string into pairs of bits (`{00, 10, 01, 11}`) packed into 64-bit integers,
+/// by using the fastest method that the running CPU supports, storing the result in `out` without allocating.
+///
+/// Integers in `out` after the packed nucleotides are not changed. Panics if `out` is too small.
+pub fn n_to_bits_into(n: &[u8], out: &mut [u64]) {
+    let len = (n.len() >> 5) + if n.len() & 31 == 0 {0} else {1};
+
+    if len > out.len() {
+        panic!("The number of nucleotides is greater than the length of the output!");
+    }
+
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    if has_avx2() {
+        unsafe {n_to_bits_movemask_kernel(n, out.as_mut_ptr())};
+        return;
+    }
+
+    out[..len].fill(0);
+    n_to_bits_lut_kernel(n, &mut out[..len]);
+}
+
+// `res` must be zeroed and large enough to hold all of the nucleotides
+#[inline]
+fn n_to_bits_lut_kernel(n: &[u8], res: &mut [u64]) {
+    assert!(n.len() <= (res.len() << 5));
 
     unsafe {
         for i in 0..n.len() {
@@ -42,17 +138,109 @@ pub fn n_to_bits_lut(n: &[u8]) -> Vec<u64> {
                 | ((*BYTE_LUT.get_unchecked(*n.get_unchecked(i) as usize) as u64) << shift);
         }
     }
+}
+
+/// Encode `{A, T/U, C, G}` from the byte string into pairs of bits (`{00, 10, 01, 11}`) packed into exactly `words`
+/// 64-bit integers, with the unused integers set to zero, by using a naive scalar method.
+///
+/// This is useful for storing fixed size records.
+pub fn n_to_bits_lut_padded(n: &[u8], words: usize) -> Vec<u64> {
+    if n.len() > (words << 5) {
+        panic!("The number of nucleotides is greater than the length of the padded output!");
+    }
 
+    let mut res = n_to_bits_lut(n);
+    res.resize(words, 0);
     res
 }
 
+/// Encode `{A, T/U, C, G}` from the byte string and its reverse complement into pairs of bits (`{00, 10, 01, 11}`)
+/// packed into 64-bit integers, in a single scalar pass over the byte string.
+///
+/// Returns the forward and the reverse complement packed sequences, along with their lengths.
+pub fn encode_both_strands(n: &[u8]) -> ((Vec<u64>, usize), (Vec<u64>, usize)) {
+    let len = (n.len() >> 5) + if n.len() & 31 == 0 {0} else {1};
+    let mut fwd = vec![0u64; len];
+    let mut rc = vec![0u64; len];
+
+    for (i, &c) in n.iter().enumerate() {
+        let bits = BYTE_LUT[c as usize] as u64;
+        fwd[i >> 5] |= bits << ((i & 31) << 1);
+
+        // complement is done by flipping the high bit
+        let j = n.len() - 1 - i;
+        rc[j >> 5] |= (bits ^ 0b10) << ((j & 31) << 1);
+    }
+
+    ((fwd, n.len()), (rc, n.len()))
+}
+
+/// Encode `{A, T/U, C, G}` from the byte string into pairs of bits (`{00, 10, 01, 11}`) packed into 64-bit integers,
+/// along with a mask that has one bit for each nucleotide that is set if the nucleotide is lowercase.
+///
+/// This keeps soft-masked regions, which can be restored with `bits_to_n_with_mask`.
+pub fn n_to_bits_with_mask(n: &[u8]) -> (Vec<u64>, Vec<u64>) {
+    let mut mask = vec![0u64; (n.len() >> 6) + if n.len() & 63 == 0 {0} else {1}];
+    let end_idx = if has_avx2() {n.len() >> 5} else {0};
+
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    if end_idx > 0 {
+        unsafe {case_mask_avx2(n, &mut mask)};
+    }
+
+    for i in (end_idx << 5)..n.len() {
+        // ASCII letters are lowercase when bit 5 is set
+        mask[i >> 6] |= (((n[i] >> 5) & 1) as u64) << (i & 63);
+    }
+
+    (encode(n), mask)
+}
+
+// only handles the full 32-byte chunks
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "avx2")]
+unsafe fn case_mask_avx2(n: &[u8], mask: &mut [u64]) {
+    let ptr = n.as_ptr() as *const __m256i;
+
+    unsafe {
+        for i in 0..(n.len() >> 5) {
+            // move the case bit to the top of each byte
+            let v = _mm256_slli_epi16(_mm256_loadu_si256(ptr.add(i)), 2);
+            let bits = (_mm256_movemask_epi8(v) as u32) as u64;
+            *mask.get_unchecked_mut(i >> 1) |= bits << ((i & 1) << 5);
+        }
+    }
+}
+
+/// Encode `{A, T/U, C, G}` from the byte string into pairs of bits (`{00, 10, 01, 11}`) packed into 64-bit integers,
+/// choosing whichever of the sequence and its reverse complement is lexicographically smaller (`A < C < T < G`).
+///
+/// Returns the packed sequence, its length, and whether the reverse complement was chosen. The forward strand is
+/// chosen if both are equal.
+pub fn canonical_sequence(n: &[u8]) -> (Vec<u64>, usize, bool) {
+    let fwd = encode(n);
+    let rc = revcomp_bits(&fwd, n.len());
+
+    if cmp_bits(&rc, n.len(), &fwd, n.len()) == Ordering::Less {
+        (rc, n.len(), true)
+    } else {
+        (fwd, n.len(), false)
+    }
+}
+
 /// Decode pairs of bits from packed 64-bit integers to get a byte string of `{A, T/U, C, G}`, by using a naive scalar
 /// method.
+#[allow(clippy::ptr_offset_with_cast)]
 pub fn bits_to_n_lut(bits: &[u64], len: usize) -> Vec<u8> {
     if len > (bits.len() << 5) {
         panic!("The length is greater than the number of nucleotides!");
     }
 
+    // allocating zero bytes is not allowed
+    if len == 0 {
+        return Vec::new();
+    }
+
     unsafe {
         let layout = alloc::Layout::from_size_align_unchecked(len, 1);
         let res_ptr = alloc::alloc(layout);
@@ -68,6 +256,158 @@ pub fn bits_to_n_lut(bits: &[u64], len: usize) -> Vec<u8> {
     }
 }
 
+/// Decode pairs of bits from packed 64-bit integers to get both a byte string of `{A, T, C, G}` and a byte string of
+/// its complement, without reversing the complement.
+///
+/// This is useful for displaying both strands side by side.
+pub fn decode_with_complement(bits: &[u64], len: usize) -> (Vec<u8>, Vec<u8>) {
+    (decode(bits, len), decode(&complement_bits(bits, len), len))
+}
+
+/// Decode pairs of bits from packed 64-bit integers to get a byte string of `{A, T, C, G}`, where each nucleotide is
+/// lowercase if its bit in the mask from `n_to_bits_with_mask` is set.
+pub fn bits_to_n_with_mask(bits: &[u64], mask: &[u64], len: usize) -> Vec<u8> {
+    if len > (mask.len() << 6) {
+        panic!("The length is greater than the length of the mask!");
+    }
+
+    let mut res = decode(bits, len);
+
+    for i in 0..len {
+        res[i] |= (((mask[i >> 6] >> (i & 63)) & 1) as u8) << 5;
+    }
+
+    res
+}
+
+/// Unpack the two-bit encodings (`{A: 0, T/U: 2, C: 1, G: 3}`) of `len` nucleotides from 64-bit integers into one
+/// byte each, without going through ASCII.
+pub fn bits_to_codes(bits: &[u64], len: usize) -> Vec<u8> {
+    if len > (bits.len() << 5) {
+        panic!("The length is greater than the number of nucleotides!");
+    }
+
+    (0..len).map(|i| get_base(bits, i)).collect()
+}
+
+/// Decode pairs of bits from packed 64-bit integers to get a byte string of `{A, T/U, C, G}`, by using a naive scalar
+/// method, overwriting the first `len` bytes of `buf`.
+///
+/// This is useful for updating the original byte string after the packed nucleotides have been modified. Bytes after
+/// `len` in `buf` are not changed.
+pub fn bits_to_n_overwrite(bits: &[u64], len: usize, buf: &mut [u8]) {
+    if len > (bits.len() << 5) {
+        panic!("The length is greater than the number of nucleotides!");
+    }
+
+    if len > buf.len() {
+        panic!("The length is greater than the length of the buffer!");
+    }
+
+    bits_to_n_lut_kernel(bits, &mut buf[..len]);
+}
+
+/// Decode pairs of bits from packed 64-bit integers to get a byte string of `{A, T/U, C, G}`, by using the fastest
+/// method that the running CPU supports, storing the result in the first `len` bytes of `out` without allocating.
+///
+/// Bytes in `out` after `len` are not changed. Panics if `out` is too small.
+pub fn bits_to_n_into(bits: &[u64], len: usize, out: &mut [u8]) {
+    if len > (bits.len() << 5) {
+        panic!("The length is greater than the number of nucleotides!");
+    }
+
+    if len > out.len() {
+        panic!("The length is greater than the length of the output!");
+    }
+
+    // only full integers are decoded with the vectorized method, so nothing is written past `len`
+    let start = if has_avx2() {len >> 5} else {0};
+
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    if start > 0 {
+        unsafe {bits_to_n_shuffle_kernel(&bits[..start], out.as_mut_ptr())};
+    }
+
+    bits_to_n_lut_kernel(&bits[start..], &mut out[(start << 5)..len]);
+}
+
+// `bits` must contain at least `res.len()` nucleotides
+#[inline]
+fn bits_to_n_lut_kernel(bits: &[u64], res: &mut [u8]) {
+    assert!(res.len() <= (bits.len() << 5));
+
+    unsafe {
+        for i in 0..res.len() {
+            let offset = i >> 5;
+            let shift = (i & 31) << 1;
+            let curr = *bits.get_unchecked(offset);
+            *res.get_unchecked_mut(i) = *BITS_LUT.get_unchecked(((curr >> shift) & 0b11) as usize);
+        }
+    }
+}
+
+/// Decode every `stride`-th nucleotide (starting from the first) of the first `len` nucleotides packed in 64-bit
+/// integers to get a byte string of `{A, T/U, C, G}`, by using a naive scalar method.
+pub fn decode_stride(bits: &[u64], len: usize, stride: usize) -> Vec<u8> {
+    if len > (bits.len() << 5) {
+        panic!("The length is greater than the number of nucleotides!");
+    }
+
+    if stride == 0 {
+        panic!("The stride must be greater than zero!");
+    }
+
+    (0..len).step_by(stride).map(|i| BITS_LUT[get_base(bits, i) as usize]).collect()
+}
+
+/// Decode only the nucleotides whose two-bit encoding satisfies `pred`, and return the index of each one and its
+/// `{A, T/U, C, G}` byte, by using a naive scalar method.
+pub fn decode_where<F: Fn(u8) -> bool>(bits: &[u64], len: usize, pred: F) -> Vec<(usize, u8)> {
+    if len > (bits.len() << 5) {
+        panic!("The length is greater than the number of nucleotides!");
+    }
+
+    (0..len).filter_map(|i| {
+        let code = get_base(bits, i);
+        if pred(code) {Some((i, BITS_LUT[code as usize]))} else {None}
+    }).collect()
+}
+
+/// Decode pairs of bits from packed 64-bit integers into two nucleotides per byte, by using a naive scalar method.
+///
+/// Each nucleotide is stored as its two-bit encoding (`{A: 0, T/U: 2, C: 1, G: 3}`) in a four-bit nibble, with the
+/// first nucleotide of each pair in the high nibble. If `len` is odd, then the low nibble of the last byte is zero.
+pub fn bits_to_packed_nibbles(bits: &[u64], len: usize) -> Vec<u8> {
+    if len > (bits.len() << 5) {
+        panic!("The length is greater than the number of nucleotides!");
+    }
+
+    let mut res = vec![0u8; (len >> 1) + (len & 1)];
+
+    for i in 0..len {
+        res[i >> 1] |= get_base(bits, i) << if i & 1 == 0 {4} else {0};
+    }
+
+    res
+}
+
+/// Nucleotides that are either a byte string or pairs of bits packed into 64-bit integers (with a length).
+#[derive(Debug, Clone, Copy)]
+pub enum Nucleotides<'a> {
+    Ascii(&'a [u8]),
+    Packed(&'a [u64], usize)
+}
+
+/// Get the nucleotides as a byte string of `{A, T/U, C, G}`, by decoding them with a naive scalar method if they are
+/// packed, or borrowing them if they are already a byte string.
+pub fn decode_cow(n: Nucleotides) -> Cow<[u8]> {
+    match n {
+        Nucleotides::Ascii(n) => Cow::Borrowed(n),
+        Nucleotides::Packed(bits, len) => Cow::Owned(bits_to_n_lut(bits, len))
+    }
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
 union AlignedArray {
     v: __m256i,
     a: [u64; 4]
@@ -76,8 +416,18 @@ union AlignedArray {
 /// Encode `{A, T/U, C, G}` from the byte string into pairs of bits (`{00, 10, 01, 11}`) packed into 64-bit integers,
 /// by using a vectorized method with the `pext` instruction.
 ///
-/// Requires AVX2 and BMI2 support.
-pub fn n_to_bits_pext(n: &[u8]) -> Vec<u64> {
+/// # Safety
+///
+/// The running CPU must support AVX2 and BMI2, which can be checked with `is_x86_feature_detected!`.
+#[allow(clippy::explicit_auto_deref, clippy::ptr_offset_with_cast)]
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "avx2,bmi2")]
+pub unsafe fn n_to_bits_pext(n: &[u8]) -> Vec<u64> {
+    // allocating zero bytes is not allowed
+    if n.is_empty() {
+        return Vec::new();
+    }
+
     let ptr = n.as_ptr() as *const __m256i;
     let end_idx = n.len() >> 5;
     let len = end_idx + if n.len() & 31 == 0 {0} else {1};
@@ -117,8 +467,18 @@ pub fn n_to_bits_pext(n: &[u8]) -> Vec<u64> {
 /// Encode `{A, T/U, C, G}` from the byte string into pairs of bits (`{00, 10, 01, 11}`) packed into 64-bit integers,
 /// by using a vectorized method with the `srli` (bit shift) instruction and merging.
 ///
-/// Requires AVX2 support.
-pub fn n_to_bits_shift(n: &[u8]) -> Vec<u64> {
+/// # Safety
+///
+/// The running CPU must support AVX2, which can be checked with `is_x86_feature_detected!`.
+#[allow(clippy::explicit_auto_deref, clippy::ptr_offset_with_cast)]
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "avx2")]
+pub unsafe fn n_to_bits_shift(n: &[u8]) -> Vec<u64> {
+    // allocating zero bytes is not allowed
+    if n.is_empty() {
+        return Vec::new();
+    }
+
     let ptr = n.as_ptr() as *const __m256i;
     let end_idx = n.len() >> 5;
     let len = end_idx + if n.len() & 31 == 0 {0} else {1};
@@ -168,15 +528,36 @@ pub fn n_to_bits_shift(n: &[u8]) -> Vec<u64> {
 /// Encode `{A, T/U, C, G}` from the byte string into pairs of bits (`{00, 10, 01, 11}`) packed into 64-bit integers,
 /// by using a vectorized method with the `permute4x64`, `unpack`, and `movemask` instructions.
 ///
-/// Requires AVX2 support.
-pub fn n_to_bits_movemask(n: &[u8]) -> Vec<u64> {
-    let ptr = n.as_ptr() as *const __m256i;
-    let end_idx = n.len() >> 5;
-    let len = end_idx + if n.len() & 31 == 0 {0} else {1};
+/// # Safety
+///
+/// The running CPU must support AVX2, which can be checked with `is_x86_feature_detected!`.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "avx2")]
+pub unsafe fn n_to_bits_movemask(n: &[u8]) -> Vec<u64> {
+    // allocating zero bytes is not allowed
+    if n.is_empty() {
+        return Vec::new();
+    }
+
+    let len = (n.len() >> 5) + if n.len() & 31 == 0 {0} else {1};
 
     unsafe {
         let layout = alloc::Layout::from_size_align_unchecked(len << 3, 8);
         let res_ptr = alloc::alloc(layout) as *mut u64;
+        n_to_bits_movemask_kernel(n, res_ptr);
+        Vec::from_raw_parts(res_ptr, len, len)
+    }
+}
+
+// `res_ptr` must point to enough integers to hold all of the nucleotides
+#[allow(clippy::ptr_offset_with_cast)]
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "avx2")]
+unsafe fn n_to_bits_movemask_kernel(n: &[u8], res_ptr: *mut u64) {
+    let ptr = n.as_ptr() as *const __m256i;
+    let end_idx = n.len() >> 5;
+
+    unsafe {
         for i in 0..end_idx as isize {
             let v = _mm256_loadu_si256(ptr.offset(i));
 
@@ -199,18 +580,28 @@ pub fn n_to_bits_movemask(n: &[u8]) -> Vec<u64> {
         }
 
         if n.len() & 31 > 0 {
-            *res_ptr.offset(end_idx as isize) = *n_to_bits_lut(&n[(end_idx << 5)..]).get_unchecked(0);
+            let mut last = [0u64];
+            n_to_bits_lut_kernel(&n[(end_idx << 5)..], &mut last);
+            *res_ptr.offset(end_idx as isize) = last[0];
         }
-
-        Vec::from_raw_parts(res_ptr, len, len)
     }
 }
 
 /// Encode `{A, T/U, C, G}` from the byte string into pairs of bits (`{00, 10, 01, 11}`) packed into 64-bit integers,
 /// by using a vectorized method with multiplication by a special mask to shift bits.
 ///
-/// Requires AVX2 support.
-pub fn n_to_bits_mul(n: &[u8]) -> Vec<u64> {
+/// # Safety
+///
+/// The running CPU must support AVX2, which can be checked with `is_x86_feature_detected!`.
+#[allow(clippy::eq_op, clippy::explicit_auto_deref, clippy::identity_op, clippy::ptr_offset_with_cast)]
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "avx2")]
+pub unsafe fn n_to_bits_mul(n: &[u8]) -> Vec<u64> {
+    // allocating zero bytes is not allowed
+    if n.is_empty() {
+        return Vec::new();
+    }
+
     let ptr = n.as_ptr() as *const __m256i;
     let end_idx = n.len() >> 5;
     let len = end_idx + if n.len() & 31 == 0 {0} else {1};
@@ -261,15 +652,36 @@ pub fn n_to_bits_mul(n: &[u8]) -> Vec<u64> {
 /// Decode pairs of bits from packed 64-bit integers to get a byte string of `{A, T/U, C, G}`, by using a vectorized
 /// method with the `srli` (bit shift) instruction and a lookup table with the `shuffle` instruction.
 ///
-/// Requires AVX2 support.
-pub fn bits_to_n_shuffle(bits: &[u64], len: usize) -> Vec<u8> {
+/// # Safety
+///
+/// The running CPU must support AVX2, which can be checked with `is_x86_feature_detected!`.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "avx2")]
+pub unsafe fn bits_to_n_shuffle(bits: &[u64], len: usize) -> Vec<u8> {
     if len > (bits.len() << 5) {
         panic!("The length is greater than the number of nucleotides!");
     }
 
+    // allocating zero bytes is not allowed
+    if len == 0 {
+        return Vec::new();
+    }
+
     unsafe {
-        let layout = alloc::Layout::from_size_align_unchecked(bits.len() << 5, 32);
-        let ptr = alloc::alloc(layout) as *mut __m256i;
+        let layout = alloc::Layout::from_size_align_unchecked(bits.len() << 5, 1);
+        let ptr = alloc::alloc(layout);
+        bits_to_n_shuffle_kernel(bits, ptr);
+        Vec::from_raw_parts(ptr, len, bits.len() << 5)
+    }
+}
+
+// `res_ptr` must point to 32 bytes for each integer in `bits`
+#[allow(clippy::ptr_offset_with_cast)]
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "avx2")]
+unsafe fn bits_to_n_shuffle_kernel(bits: &[u64], res_ptr: *mut u8) {
+    unsafe {
+        let ptr = res_ptr as *mut __m256i;
 
         let shuffle_mask = _mm256_set_epi32(0x07070707, 0x06060606, 0x05050505, 0x04040404, 0x03030303, 0x02020202, 0x01010101, 0x00000000);
         let lo_mask = _mm256_set1_epi16(0b0000110000000011);
@@ -295,26 +707,34 @@ pub fn bits_to_n_shuffle(bits: &[u64], len: usize) -> Vec<u8> {
 
             // use lookup table to convert nucleotide bits to bytes
             let v = _mm256_shuffle_epi8(lut, v);
-            _mm256_store_si256(ptr.offset(i as isize), v);
+            _mm256_storeu_si256(ptr.offset(i as isize), v);
         }
-
-        Vec::from_raw_parts(ptr as *mut u8, len, bits.len() << 5)
     }
 }
 
 /// Decode pairs of bits from packed 64-bit integers to get a byte string of `{A, T/U, C, G}`, by using a vectorized
 /// method with the `pdep` instruction and a lookup table with the `shuffle` instruction.
 ///
-/// Requires AVX2 and BMI2 support.
-pub fn bits_to_n_pdep(bits: &[u64], len: usize) -> Vec<u8> {
+/// # Safety
+///
+/// The running CPU must support AVX2 and BMI2, which can be checked with `is_x86_feature_detected!`.
+#[allow(clippy::ptr_offset_with_cast)]
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "avx2,bmi2")]
+pub unsafe fn bits_to_n_pdep(bits: &[u64], len: usize) -> Vec<u8> {
     if len > (bits.len() << 5) {
         panic!("The length is greater than the number of nucleotides!");
     }
 
+    // allocating zero bytes is not allowed
+    if len == 0 {
+        return Vec::new();
+    }
+
     let scatter_mask = 0x0303030303030303u64;
 
     unsafe {
-        let layout = alloc::Layout::from_size_align_unchecked(bits.len() << 5, 32);
+        let layout = alloc::Layout::from_size_align_unchecked(bits.len() << 5, 1);
         let ptr = alloc::alloc(layout) as *mut __m256i;
 
         let lut_i32 = (b'A' as i32) | ((b'C' as i32) << 8) | ((b'T' as i32) << 16) | ((b'G' as i32) << 24);
@@ -332,7 +752,7 @@ pub fn bits_to_n_pdep(bits: &[u64], len: usize) -> Vec<u8> {
 
             // lookup table from nucleotide bits to bytes
             let v = _mm256_shuffle_epi8(lut, v);
-            _mm256_store_si256(ptr.offset(i as isize), v);
+            _mm256_storeu_si256(ptr.offset(i as isize), v);
         }
 
         Vec::from_raw_parts(ptr as *mut u8, len, bits.len() << 5)
@@ -342,14 +762,24 @@ pub fn bits_to_n_pdep(bits: &[u64], len: usize) -> Vec<u8> {
 /// Decode pairs of bits from packed 64-bit integers to get a byte string of `{A, T/U, C, G}`, by using a vectorized
 /// method with the `clmul` (carry-less multiplication) instruction.
 ///
-/// Requires SSSE3 and PCLMULQDQ support.
-pub fn bits_to_n_clmul(bits: &[u64], len: usize) -> Vec<u8> {
+/// # Safety
+///
+/// The running CPU must support SSSE3 and PCLMULQDQ, which can be checked with `is_x86_feature_detected!`.
+#[allow(clippy::eq_op, clippy::identity_op, clippy::ptr_offset_with_cast)]
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "ssse3,pclmulqdq")]
+pub unsafe fn bits_to_n_clmul(bits: &[u64], len: usize) -> Vec<u8> {
     if len > (bits.len() << 5) {
         panic!("The length is greater than the number of nucleotides!");
     }
 
+    // allocating zero bytes is not allowed
+    if len == 0 {
+        return Vec::new();
+    }
+
     unsafe {
-        let layout = alloc::Layout::from_size_align_unchecked(bits.len() << 5, 16);
+        let layout = alloc::Layout::from_size_align_unchecked(bits.len() << 5, 1);
         let ptr = alloc::alloc(layout) as *mut __m128i;
 
         let lo_shuffle_mask = _mm_set_epi32(0xFFFFFF03u32 as i32, 0xFFFFFF02u32 as i32, 0xFFFFFF01u32 as i32, 0xFFFFFF00u32 as i32);
@@ -395,8 +825,8 @@ pub fn bits_to_n_clmul(bits: &[u64], len: usize) -> Vec<u8> {
             // use lookup table to convert nucleotide bits to bytes
             let lo_v = _mm_shuffle_epi8(lut, lo_v);
             let hi_v = _mm_shuffle_epi8(lut, hi_v);
-            _mm_store_si128(ptr.offset((i << 1) as isize), lo_v);
-            _mm_store_si128(ptr.offset(((i << 1) + 1) as isize), hi_v);
+            _mm_storeu_si128(ptr.offset((i << 1) as isize), lo_v);
+            _mm_storeu_si128(ptr.offset(((i << 1) + 1) as isize), hi_v);
         }
 
         Vec::from_raw_parts(ptr as *mut u8, len, bits.len() << 5)
@@ -409,6 +839,12 @@ pub fn bits_to_n_clmul(bits: &[u64], len: usize) -> Vec<u8> {
 mod tests {
     use super::*;
 
+    // decoding gives uppercase nucleotides, with `U` decoded as `T`
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    fn n_to_bits_round_trip(n: &[u8]) -> Vec<u8> {
+        n.iter().map(|&c| if c.eq_ignore_ascii_case(&b'U') {b'T'} else {c.to_ascii_uppercase()}).collect()
+    }
+
     #[test]
     fn test_n_to_bits_lut() {
         assert_eq!(n_to_bits_lut(b"ATCGATCGATCGATCGATCGATCGATCGATCG"),
@@ -416,6 +852,118 @@ mod tests {
         assert_eq!(n_to_bits_lut(b"ATCG"), vec![0b11011000]);
     }
 
+    #[test]
+    fn test_n_to_bits_checked() {
+        let n = b"ATCGATCGATCGATCGATCGATCGATCGATCGatcgu";
+        assert_eq!(n_to_bits_checked(n), Ok(n_to_bits_lut(n)));
+        assert_eq!(n_to_bits_checked(b"ATCN"), Err(InvalidNucleotide{index: 3, byte: b'N'}));
+        assert_eq!(n_to_bits_checked(b"ATCGATCGATCGATCGATCGATCGATCGAT\nCG"),
+                Err(InvalidNucleotide{index: 30, byte: b'\n'}));
+        assert!(InvalidNucleotide{index: 3, byte: b'N'}.to_string().contains("n_to_bits2"));
+        assert!(!InvalidNucleotide{index: 3, byte: b' '}.to_string().contains("n_to_bits2"));
+        assert_eq!(n_to_bits_checked(b""), Ok(vec![]));
+    }
+
+    #[test]
+    fn test_codes_to_bits() {
+        assert_eq!(codes_to_bits(&[0, 2, 1, 3]), n_to_bits_lut(b"ATCG"));
+        assert_eq!(codes_to_bits(&[0, 2, 1, 3].repeat(9)), n_to_bits_lut(&b"ATCG".repeat(9)));
+        assert_eq!(codes_to_bits(&[]), vec![]);
+    }
+
+    #[test]
+    fn test_n_to_bits_lut_reuse() {
+        let mut out = Vec::new();
+        n_to_bits_lut_reuse(b"ATCGATCGATCGATCGATCGATCGATCGATCGATCG", &mut out);
+        assert_eq!(out, n_to_bits_lut(b"ATCGATCGATCGATCGATCGATCGATCGATCGATCG"));
+        let ptr = out.as_ptr();
+        n_to_bits_lut_reuse(b"ATCG", &mut out);
+        assert_eq!(out, vec![0b11011000]);
+        n_to_bits_lut_reuse(b"GGCATCGATCGATCGATCGATCGATCGATCGATCG", &mut out);
+        assert_eq!(out, n_to_bits_lut(b"GGCATCGATCGATCGATCGATCGATCGATCGATCG"));
+        assert_eq!(out.as_ptr(), ptr);
+        n_to_bits_lut_reuse(b"", &mut out);
+        assert_eq!(out, vec![]);
+    }
+
+    #[test]
+    fn test_n_to_bits_into() {
+        let n = b"ATCGATCGATCGATCGATCGATCGATCGATCGATCGATCGATCGATCGATCGATCGATCGATCGATCGA";
+        let mut out = vec![!0u64; 4];
+        n_to_bits_into(n, &mut out);
+        assert_eq!(&out[..3], &n_to_bits_lut(n)[..]);
+        assert_eq!(out[3], !0);
+
+        n_to_bits_into(b"ATCG", &mut out);
+        assert_eq!(out[0], 0b11011000);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_n_to_bits_into_too_small() {
+        n_to_bits_into(b"ATCGATCGATCGATCGATCGATCGATCGATCGA", &mut [0u64; 1]);
+    }
+
+    #[test]
+    fn test_n_to_bits_lut_padded() {
+        assert_eq!(n_to_bits_lut_padded(b"ATCGATCGAT", 3), vec![0b10001101100011011000, 0, 0]);
+        assert_eq!(n_to_bits_lut_padded(b"", 1), vec![0]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_n_to_bits_lut_padded_too_long() {
+        n_to_bits_lut_padded(b"ATCGATCGATCGATCGATCGATCGATCGATCGA", 1);
+    }
+
+    #[test]
+    fn test_encode_both_strands() {
+        let n = b"AATCGGATCGATCGATCGATCGATCGATCGATCGAC";
+        let ((fwd, fwd_len), (rc, rc_len)) = encode_both_strands(n);
+        assert_eq!(fwd, n_to_bits_lut(n));
+        assert_eq!(fwd_len, 36);
+        assert_eq!(rc_len, 36);
+        assert_eq!(bits_to_n_lut(&rc, rc_len), b"GTCGATCGATCGATCGATCGATCGATCGATCCGATT".to_vec());
+    }
+
+    #[test]
+    fn test_n_to_bits_with_mask() {
+        let n = b"ATCGatcgATCGATCGATCGATCGATCGATCGATCGATCGATCGATCGATCGATCGATCGATCGatcgatCG";
+        let (bits, mask) = n_to_bits_with_mask(n);
+        assert_eq!(bits, n_to_bits_lut(n));
+        assert_eq!(mask, vec![0b11110000, 0b00111111]);
+
+        let (bits, mask) = n_to_bits_with_mask(b"aC");
+        assert_eq!(bits, vec![0b0100]);
+        assert_eq!(mask, vec![0b01]);
+        assert_eq!(n_to_bits_with_mask(b""), (vec![], vec![]));
+    }
+
+    #[test]
+    fn test_bits_to_n_with_mask() {
+        let n = b"ATCGatcgATCGATCGATCGATCGATCGATCGATCGATCGATCGATCGATCGATCGATCGATCGatcgatCG";
+        let (bits, mask) = n_to_bits_with_mask(n);
+        assert_eq!(bits_to_n_with_mask(&bits, &mask, n.len()), n.to_vec());
+        assert_eq!(bits_to_n_with_mask(&bits, &mask, 6), b"ATCGat".to_vec());
+        assert_eq!(bits_to_n_with_mask(&[], &[], 0), vec![]);
+    }
+
+    #[test]
+    fn test_canonical_sequence() {
+        let (bits, len, rc) = canonical_sequence(b"GATCGATCGATCGATCGATCGATCGATCGATCAAA");
+        assert_eq!(bits_to_n_lut(&bits, len), b"TTTGATCGATCGATCGATCGATCGATCGATCGATC".to_vec());
+        assert!(rc);
+
+        let (bits, len, rc) = canonical_sequence(b"AAAC");
+        assert_eq!(bits_to_n_lut(&bits, len), b"AAAC".to_vec());
+        assert!(!rc);
+
+        // palindromes keep the forward strand
+        assert_eq!(canonical_sequence(b"ACGT"), (n_to_bits_lut(b"ACGT"), 4, false));
+        assert_eq!(canonical_sequence(b""), (vec![], 0, false));
+    }
+
+    #[allow(clippy::useless_vec)]
     #[test]
     fn test_bits_to_n_lut() {
         assert_eq!(bits_to_n_lut(&vec![0b1101100011011000110110001101100011011000110110001101100011011000], 32),
@@ -423,48 +971,156 @@ mod tests {
     }
 
     #[test]
+    fn test_decode_with_complement() {
+        let n = b"AATCGGATCGATCGATCGATCGATCGATCGATCGAC";
+        let (fwd, comp) = decode_with_complement(&n_to_bits_lut(n), 36);
+        assert_eq!(fwd, n.to_vec());
+        assert_eq!(comp, b"TTAGCCTAGCTAGCTAGCTAGCTAGCTAGCTAGCTG".to_vec());
+        assert_eq!(comp, crate::ascii::complement_ascii(&fwd));
+        assert_eq!(decode_with_complement(&[], 0), (vec![], vec![]));
+        assert_eq!(decode_with_complement(&n_to_bits_lut(n), 0), (vec![], vec![]));
+    }
+
+    #[test]
+    fn test_bits_to_codes() {
+        assert_eq!(bits_to_codes(&n_to_bits_lut(b"ATCG"), 4), vec![0, 2, 1, 3]);
+        let codes = [0, 2, 1, 3].repeat(9);
+        assert_eq!(bits_to_codes(&codes_to_bits(&codes), 35), codes[..35].to_vec());
+        assert_eq!(bits_to_codes(&[], 0), vec![]);
+    }
+
+    #[test]
+    fn test_bits_to_n_into() {
+        let n = b"ATCGATCGATCGATCGATCGATCGATCGATCGATCGATCGATCGATCGATCGATCGATCGATCGATCGA";
+        let bits = n_to_bits_lut(n);
+        let mut out = vec![b'N'; 80];
+        bits_to_n_into(&bits, n.len(), &mut out);
+        assert_eq!(&out[..n.len()], &n[..]);
+        assert!(out[n.len()..].iter().all(|&c| c == b'N'));
+
+        let mut out = vec![b'N'; 37];
+        bits_to_n_into(&bits, 37, &mut out);
+        assert_eq!(&out[..], &n[..37]);
+    }
+
+    #[test]
+    fn test_bits_to_n_overwrite() {
+        let mut buf = b"ATCGATCGATCGATCGATCGATCGATCGATCGATCGNN".to_vec();
+        let mut bits = n_to_bits_lut(&buf[..36]);
+        // change the first nucleotide to G
+        bits[0] |= 0b11;
+        bits_to_n_overwrite(&bits, 36, &mut buf);
+        assert_eq!(buf, b"GTCGATCGATCGATCGATCGATCGATCGATCGATCGNN".to_vec());
+    }
+
+    #[test]
+    fn test_decode_stride() {
+        let bits = n_to_bits_lut(b"ATCGATCGATCGATCGATCGATCGATCGATCGATCG");
+        assert_eq!(decode_stride(&bits, 36, 3), b"AGCTAGCTAGCT");
+        assert_eq!(decode_stride(&bits, 35, 3), b"AGCTAGCTAGCT");
+        assert_eq!(decode_stride(&bits, 34, 1), &b"ATCGATCGATCGATCGATCGATCGATCGATCGAT"[..]);
+    }
+
+    #[test]
+    fn test_decode_where() {
+        let bits = n_to_bits_lut(b"ATCGATCGAAAAAAAAAAAAAAAAAAAAAAAAAAGC");
+        // C and G both have their low bit set
+        let gc = decode_where(&bits, 36, |code| code & 1 == 1);
+        assert_eq!(gc, vec![(2, b'C'), (3, b'G'), (6, b'C'), (7, b'G'), (34, b'G'), (35, b'C')]);
+        assert_eq!(decode_where(&bits, 34, |code| code == 0b10), vec![(1, b'T'), (5, b'T')]);
+        assert_eq!(decode_where(&bits, 36, |_| false), vec![]);
+    }
+
+    #[test]
+    fn test_bits_to_packed_nibbles() {
+        let bits = n_to_bits_lut(b"ATCGATCGATCGATCGATCGATCGATCGATCGATCGG");
+        assert_eq!(bits_to_packed_nibbles(&bits, 4), vec![0x02, 0x13]);
+        assert_eq!(bits_to_packed_nibbles(&bits, 3), vec![0x02, 0x10]);
+        assert_eq!(bits_to_packed_nibbles(&bits, 37), [[0x02, 0x13].repeat(9), vec![0x30]].concat());
+        assert_eq!(bits_to_packed_nibbles(&bits, 0), vec![]);
+    }
+
+    #[test]
+    fn test_decode_cow() {
+        let bits = n_to_bits_lut(b"ATCGATCGATCGATCGATCGATCGATCGATCGATCG");
+        let n = decode_cow(Nucleotides::Packed(&bits, 35));
+        assert!(matches!(n, Cow::Owned(_)));
+        assert_eq!(&*n, &b"ATCGATCGATCGATCGATCGATCGATCGATCGATC"[..]);
+
+        let n = decode_cow(Nucleotides::Ascii(b"ATCG"));
+        assert!(matches!(n, Cow::Borrowed(_)));
+        assert_eq!(&*n, b"ATCG");
+    }
+
+    #[test]
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
     fn test_n_to_bits_pext() {
-        assert_eq!(n_to_bits_pext(b"ATCGATCGATCGATCGATCGATCGATCGATCG"),
+        assert_eq!(unsafe {n_to_bits_pext(b"ATCGATCGATCGATCGATCGATCGATCGATCG")},
                 vec![0b1101100011011000110110001101100011011000110110001101100011011000]);
-        assert_eq!(n_to_bits_pext(b"ATCG"), vec![0b11011000]);
+        assert_eq!(unsafe {n_to_bits_pext(b"ATCG")}, vec![0b11011000]);
     }
 
     #[test]
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
     fn test_n_to_bits_shift() {
-        assert_eq!(n_to_bits_shift(b"ATCGATCGATCGATCGATCGATCGATCGATCG"),
+        assert_eq!(unsafe {n_to_bits_shift(b"ATCGATCGATCGATCGATCGATCGATCGATCG")},
                 vec![0b1101100011011000110110001101100011011000110110001101100011011000]);
-        assert_eq!(n_to_bits_shift(b"ATCG"), vec![0b11011000]);
+        assert_eq!(unsafe {n_to_bits_shift(b"ATCG")}, vec![0b11011000]);
+
+        // lengths that are not multiples of 32 round trip
+        let n = b"ATCGGATTACAUGCCATGATCGATCGTTTTAGCatcgatcggattacagatcgatcgtacgtgcaGTCAAAGTCGTTAGCAT";
+        for len in 1..=n.len() {
+            let bits = unsafe {n_to_bits_shift(&n[..len])};
+            assert_eq!(bits, n_to_bits_lut(&n[..len]));
+            assert_eq!(bits_to_n_lut(&bits, len), n_to_bits_round_trip(&n[..len]));
+        }
     }
 
     #[test]
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
     fn test_n_to_bits_movemask() {
-        assert_eq!(n_to_bits_movemask(b"ATCGATCGATCGATCGATCGATCGATCGATCG"),
+        assert_eq!(unsafe {n_to_bits_movemask(b"ATCGATCGATCGATCGATCGATCGATCGATCG")},
                 vec![0b1101100011011000110110001101100011011000110110001101100011011000]);
-        assert_eq!(n_to_bits_movemask(b"ATCG"), vec![0b11011000]);
+        assert_eq!(unsafe {n_to_bits_movemask(b"ATCG")}, vec![0b11011000]);
+
+        // lengths that are not multiples of 32 round trip
+        let n = b"ATCGGATTACAUGCCATGATCGATCGTTTTAGCatcgatcggattacagatcgatcgtacgtgcaGTCAAAGTCGTTAGCAT";
+        for len in 1..=n.len() {
+            let bits = unsafe {n_to_bits_movemask(&n[..len])};
+            assert_eq!(bits, n_to_bits_lut(&n[..len]));
+            assert_eq!(bits_to_n_lut(&bits, len), n_to_bits_round_trip(&n[..len]));
+        }
     }
 
     #[test]
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
     fn test_n_to_bits_mul() {
-        assert_eq!(n_to_bits_mul(b"ATCGATCGATCGATCGATCGATCGATCGATCG"),
+        assert_eq!(unsafe {n_to_bits_mul(b"ATCGATCGATCGATCGATCGATCGATCGATCG")},
                 vec![0b1101100011011000110110001101100011011000110110001101100011011000]);
-        assert_eq!(n_to_bits_mul(b"ATCG"), vec![0b11011000]);
+        assert_eq!(unsafe {n_to_bits_mul(b"ATCG")}, vec![0b11011000]);
     }
 
+    #[allow(clippy::useless_vec)]
     #[test]
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
     fn test_bits_to_n_shuffle() {
-        assert_eq!(bits_to_n_shuffle(&vec![0b1101100011011000110110001101100011011000110110001101100011011000], 32),
+        assert_eq!(unsafe {bits_to_n_shuffle(&vec![0b1101100011011000110110001101100011011000110110001101100011011000], 32)},
                 "ATCGATCGATCGATCGATCGATCGATCGATCG".as_bytes());
     }
 
+    #[allow(clippy::useless_vec)]
     #[test]
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
     fn test_bits_to_n_pdep() {
-        assert_eq!(bits_to_n_pdep(&vec![0b1101100011011000110110001101100011011000110110001101100011011000], 32),
+        assert_eq!(unsafe {bits_to_n_pdep(&vec![0b1101100011011000110110001101100011011000110110001101100011011000], 32)},
                 "ATCGATCGATCGATCGATCGATCGATCGATCG".as_bytes());
     }
 
+    #[allow(clippy::useless_vec)]
     #[test]
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
     fn test_bits_to_n_clmul() {
-        assert_eq!(bits_to_n_clmul(&vec![0b1101100011011000110110001101100011011000110110001101100011011000], 32),
+        assert_eq!(unsafe {bits_to_n_clmul(&vec![0b1101100011011000110110001101100011011000110110001101100011011000], 32)},
                 "ATCGATCGATCGATCGATCGATCGATCGATCG".as_bytes());
     }
 }
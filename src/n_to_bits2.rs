@@ -4,6 +4,12 @@ use std::arch::x86::*;
 use std::arch::x86_64::*;
 
 use std::alloc;
+use std::error::Error;
+use std::fmt;
+
+use crate::ascii::validate_dna_n;
+use crate::features::encode2;
+use crate::n_to_bits::InvalidNucleotide;
 
 static BYTE_LUT: [u8; 128] = {
     let mut lut = [0u8; 128];
@@ -34,8 +40,34 @@ static BITS_LUT: [u8; 5] = {
 
 /// Encode each triplet of `{A, T/U, C, G, N}` from the byte string into 7 bits, then pack every 9 triplets into
 /// a single 64-bit integer, by using a naive scalar method.
+#[allow(clippy::manual_is_multiple_of)]
 pub fn n_to_bits2_lut(n: &[u8]) -> Vec<u64> {
     let mut res = vec![0u64; (n.len() / 27) + if n.len() % 27 == 0 {0} else {1}];
+    n_to_bits2_lut_kernel(n, &mut res);
+    res
+}
+
+/// Encode each triplet of `{A, T/U, C, G, N}` from the byte string into 7 bits, then pack every 9 triplets into
+/// a single 64-bit integer, by using a naive scalar method, storing the result in `out` without allocating.
+///
+/// Integers in `out` after the packed nucleotides are not changed. Panics if `out` is too small.
+#[allow(clippy::manual_is_multiple_of)]
+pub fn n_to_bits2_into(n: &[u8], out: &mut [u64]) {
+    let len = (n.len() / 27) + if n.len() % 27 == 0 {0} else {1};
+
+    if len > out.len() {
+        panic!("The number of nucleotides is greater than the length of the output!");
+    }
+
+    out[..len].fill(0);
+    n_to_bits2_lut_kernel(n, &mut out[..len]);
+}
+
+// `res` must be zeroed and large enough to hold all of the nucleotides
+#[inline]
+fn n_to_bits2_lut_kernel(n: &[u8], res: &mut [u64]) {
+    assert!(n.len() <= res.len() * 27);
+
     let len = n.len() / 3;
 
     unsafe {
@@ -69,30 +101,166 @@ pub fn n_to_bits2_lut(n: &[u8]) -> Vec<u64> {
             *res.get_unchecked_mut(res_offset) = *res.get_unchecked(res_offset) | (encoding << res_shift);
         }
     }
+}
+
+/// Encode each triplet of `{A, T/U, C, G, N}` from the byte string into 7 bits, then pack every 9 triplets into
+/// a single 64-bit integer, after checking that every byte is one of `{A, T/U, C, G, N}` (in either case).
+///
+/// The bytes are checked and encoded with the fastest methods that the running CPU supports.
+pub fn n_to_bits2_checked(n: &[u8]) -> Result<Vec<u64>, InvalidNucleotide> {
+    match validate_dna_n(n) {
+        Some(index) => Err(InvalidNucleotide{index, byte: n[index]}),
+        None => Ok(encode2(n))
+    }
+}
+
+/// Concatenate byte strings of `{A, T/U, C, G, N}` with a single `N` between each pair of adjacent byte strings, then
+/// encode each triplet into 7 bits and pack every 9 triplets into a single 64-bit integer, by using a naive scalar
+/// method.
+///
+/// Returns the packed sequence, its length, and the index where each byte string starts in the concatenated sequence.
+pub fn concat_with_separators(seqs: &[&[u8]]) -> (Vec<u64>, usize, Vec<usize>) {
+    let total = seqs.iter().map(|s| s.len()).sum::<usize>() + seqs.len().saturating_sub(1);
+    let mut concat = Vec::with_capacity(total);
+    let mut starts = Vec::with_capacity(seqs.len());
+
+    for (i, s) in seqs.iter().enumerate() {
+        if i > 0 {
+            concat.push(b'N');
+        }
+
+        starts.push(concat.len());
+        concat.extend_from_slice(s);
+    }
+
+    (n_to_bits2_lut(&concat), total, starts)
+}
+
+/// Set the nucleotides with quality scores less than `min_q` to `N` in a sequence of `{A, T/U, C, G, N}` where every
+/// triplet is encoded into 7 bits and every 9 triplets are packed into a single 64-bit integer.
+///
+/// The quality scores are compared directly with `min_q`, so they should use the same Phred offset.
+pub fn mask_low_quality(bits: &[u64], len: usize, qual: &[u8], min_q: u8) -> Vec<u64> {
+    if len > (bits.len() * 27) {
+        panic!("The length is greater than the number of nucleotides!");
+    }
+
+    if qual.len() < len {
+        panic!("There are less quality scores than nucleotides!");
+    }
+
+    let mut res = bits.to_vec();
+
+    for (i, qs) in qual[..len].chunks(3).enumerate() {
+        if qs.iter().all(|&q| q >= min_q) {
+            continue;
+        }
+
+        let offset = i / 9;
+        let shift = (i % 9) * 7;
+        let curr = (res[offset] >> shift) & 0b01111111;
+        let mut digits = [curr % 5, (curr / 5) % 5, curr / 25];
+
+        for (d, &q) in digits.iter_mut().zip(qs) {
+            if q < min_q {
+                *d = 0b100;
+            }
+        }
+
+        let encoding = digits[0] + digits[1] * 5 + digits[2] * 25;
+        res[offset] = (res[offset] & !(0b01111111 << shift)) | (encoding << shift);
+    }
+
+    res
+}
+
+/// Get the indices of all of the `N` nucleotides in a sequence of length `len` that is packed by `n_to_bits2_lut`.
+pub fn n_positions(bits: &[u64], len: usize) -> Vec<usize> {
+    if len > (bits.len() * 27) {
+        panic!("The length is greater than the number of nucleotides!");
+    }
+
+    let mut res = Vec::new();
+
+    for i in (0..len).step_by(3) {
+        let curr = (bits[i / 27] >> (((i / 3) % 9) * 7)) & 0b01111111;
+        let digits = [curr % 5, (curr / 5) % 5, curr / 25];
+
+        // the final triplet may be partial
+        for (j, &d) in digits.iter().enumerate().take(len - i) {
+            if d == 0b100 {
+                res.push(i + j);
+            }
+        }
+    }
 
     res
 }
 
 /// Decode the 9 triplets of `{A, T/U, C, G, N}` that are packed into every 64-bit integer into a byte string,
 /// by using a naive scalar method.
+///
+/// Each 7-bit chunk that is not a valid triplet is decoded into `NNN`, like in `bits_to_n2_lossy`.
+#[allow(clippy::manual_is_multiple_of)]
 pub fn bits_to_n2_lut(bits: &[u64], len: usize) -> Vec<u8> {
     if len > (bits.len() * 27) {
         panic!("The length is greater than the number of nucleotides!");
     }
 
+    // allocating zero bytes is not allowed
+    if len == 0 {
+        return Vec::new();
+    }
+
     let triplets = len / 3 + if len % 3 == 0 {0} else {1};
 
     unsafe {
         let layout = alloc::Layout::from_size_align_unchecked(bits.len() * 27, 1);
         let res_ptr = alloc::alloc(layout);
+        bits_to_n2_lut_kernel(bits, 0, triplets, res_ptr);
+        Vec::from_raw_parts(res_ptr, len, bits.len() * 27)
+    }
+}
+
+/// Decode the 9 triplets of `{A, T/U, C, G, N}` that are packed into every 64-bit integer into a byte string,
+/// by using a naive scalar method, storing the result in the first `len` bytes of `out` without allocating.
+///
+/// Each 7-bit chunk that is not a valid triplet is decoded into `NNN`. Bytes in `out` after `len` are not changed.
+/// Panics if `out` is too small.
+#[allow(clippy::manual_is_multiple_of)]
+pub fn bits_to_n2_into(bits: &[u64], len: usize, out: &mut [u8]) {
+    if len > (bits.len() * 27) {
+        panic!("The length is greater than the number of nucleotides!");
+    }
+
+    if len > out.len() {
+        panic!("The length is greater than the length of the output!");
+    }
+
+    let full = len / 3;
 
-        for i in 0..triplets {
-            let idx = (i * 3) as isize;
+    unsafe {bits_to_n2_lut_kernel(bits, 0, full, out.as_mut_ptr())};
+
+    // the last triplet may be incomplete, so it is decoded separately to avoid writing past `len`
+    if len % 3 > 0 {
+        let mut last = [0u8; 3];
+        unsafe {bits_to_n2_lut_kernel(bits, full, full + 1, last.as_mut_ptr())};
+        out[(full * 3)..len].copy_from_slice(&last[..(len % 3)]);
+    }
+}
+
+// decode the triplets in `[start, end)`, where `res_ptr` must point to `(end - start) * 3` bytes
+#[inline]
+unsafe fn bits_to_n2_lut_kernel(bits: &[u64], start: usize, end: usize, res_ptr: *mut u8) {
+    unsafe {
+        for i in start..end {
+            let idx = ((i - start) * 3) as isize;
             let offset = i / 9;
             let shift = (i % 9) * 7;
 
             // encoding = c * 5^2 + b * 5^1 + a * 5^0
-            let curr = (*bits.get_unchecked(offset) >> shift) & 0b01111111;
+            // invalid encodings are clamped to 124, which is `NNN`, so the lookup table is never indexed out of bounds
+            let curr = ((*bits.get_unchecked(offset) >> shift) & 0b01111111).min(124);
             let a = (curr % 5) as usize;
             let b = ((curr / 5) % 5) as usize;
             let c = (curr / 25) as usize;
@@ -101,11 +269,91 @@ pub fn bits_to_n2_lut(bits: &[u64], len: usize) -> Vec<u8> {
             *res_ptr.offset(idx + 1) = *BITS_LUT.get_unchecked(b);
             *res_ptr.offset(idx + 2) = *BITS_LUT.get_unchecked(c);
         }
+    }
+}
 
-        Vec::from_raw_parts(res_ptr, len, bits.len() * 27)
+/// Error for a 7-bit chunk of packed data that does not encode a triplet of `{A, T/U, C, G, N}`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvalidTriplet {
+    /// Index of the first nucleotide in the triplet.
+    pub index: usize,
+    /// Value of the 7-bit chunk, which is at least 125.
+    pub value: u8
+}
+
+impl fmt::Display for InvalidTriplet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid triplet encoding {} for the nucleotides at index {}", self.value, self.index)
+    }
+}
+
+impl Error for InvalidTriplet {}
+
+/// Decode the 9 triplets of `{A, T/U, C, G, N}` that are packed into every 64-bit integer into a byte string,
+/// by using a naive scalar method, while checking that each 7-bit chunk encodes a valid triplet.
+///
+/// This is safe to use on corrupted or untrusted data.
+#[allow(clippy::manual_is_multiple_of)]
+pub fn try_bits_to_n2_lut(bits: &[u64], len: usize) -> Result<Vec<u8>, InvalidTriplet> {
+    if len > (bits.len() * 27) {
+        panic!("The length is greater than the number of nucleotides!");
+    }
+
+    let triplets = len / 3 + if len % 3 == 0 {0} else {1};
+    let mut res = Vec::with_capacity(triplets * 3);
+
+    for i in 0..triplets {
+        let offset = i / 9;
+        let shift = (i % 9) * 7;
+        let curr = (bits[offset] >> shift) & 0b01111111;
+
+        // the largest valid encoding is 4 * 5^2 + 4 * 5^1 + 4 * 5^0 = 124
+        if curr >= 125 {
+            return Err(InvalidTriplet{index: i * 3, value: curr as u8});
+        }
+
+        res.push(BITS_LUT[(curr % 5) as usize]);
+        res.push(BITS_LUT[((curr / 5) % 5) as usize]);
+        res.push(BITS_LUT[(curr / 25) as usize]);
+    }
+
+    res.truncate(len);
+    Ok(res)
+}
+
+/// Decode the 9 triplets of `{A, T/U, C, G, N}` that are packed into every 64-bit integer into a byte string,
+/// by using a naive scalar method, while decoding each 7-bit chunk that is not a valid triplet into `NNN`.
+///
+/// This is the lenient counterpart to `try_bits_to_n2_lut`, and it is safe to use for displaying corrupted data.
+#[allow(clippy::manual_is_multiple_of)]
+pub fn bits_to_n2_lossy(bits: &[u64], len: usize) -> Vec<u8> {
+    if len > (bits.len() * 27) {
+        panic!("The length is greater than the number of nucleotides!");
+    }
+
+    let triplets = len / 3 + if len % 3 == 0 {0} else {1};
+    let mut res = Vec::with_capacity(triplets * 3);
+
+    for i in 0..triplets {
+        let offset = i / 9;
+        let shift = (i % 9) * 7;
+        let curr = (bits[offset] >> shift) & 0b01111111;
+
+        if curr >= 125 {
+            res.extend_from_slice(b"NNN");
+            continue;
+        }
+
+        res.push(BITS_LUT[(curr % 5) as usize]);
+        res.push(BITS_LUT[((curr / 5) % 5) as usize]);
+        res.push(BITS_LUT[(curr / 25) as usize]);
     }
+
+    res.truncate(len);
+    res
 }
 
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
 union AlignedArray {
     v: __m256i,
     a: [u64; 4]
@@ -114,8 +362,18 @@ union AlignedArray {
 /// Encode each triplet of `{A, T/U, C, G, N}` from the byte string into 7 bits, then pack every 9 triplets into
 /// a single 64-bit integer, by using a vectorized method with the `shuffle`, `maddubs`, and `pext` instructions.
 ///
-/// Requires AVX2 and BMI2 support.
-pub fn n_to_bits2_pext(n: &[u8]) -> Vec<u64> {
+/// # Safety
+///
+/// The running CPU must support AVX2 and BMI2, which can be checked with `is_x86_feature_detected!`.
+#[allow(clippy::explicit_auto_deref, clippy::manual_is_multiple_of, clippy::ptr_offset_with_cast)]
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "avx2,bmi2")]
+pub unsafe fn n_to_bits2_pext(n: &[u8]) -> Vec<u64> {
+    // allocating zero bytes is not allowed
+    if n.is_empty() {
+        return Vec::new();
+    }
+
     let mut ptr = n.as_ptr();
     let end_idx = if n.len() < 5 {0} else {(n.len() - 5) / 27};
     let len = (n.len() / 27) + if n.len() % 27 == 0 {0} else {1};
@@ -192,14 +450,19 @@ pub fn n_to_bits2_pext(n: &[u8]) -> Vec<u64> {
 /// by using a vectorized method with fast modulo/division through multiplication and the `shuffle` and `pdep`
 /// instructions.
 ///
-/// Requires AVX2 and BMI2 support.
-pub fn bits_to_n2_pdep(bits: &[u64], len: usize) -> Vec<u8> {
+/// # Safety
+///
+/// The running CPU must support AVX2 and BMI2, which can be checked with `is_x86_feature_detected!`.
+#[allow(clippy::identity_op)]
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "avx2,bmi2")]
+pub unsafe fn bits_to_n2_pdep(bits: &[u64], len: usize) -> Vec<u8> {
     if len > (bits.len() * 27) {
         panic!("The length is greater than the number of nucleotides!");
     }
 
     unsafe {
-        let layout = alloc::Layout::from_size_align_unchecked(bits.len() * 27 + 5, 32);
+        let layout = alloc::Layout::from_size_align_unchecked(bits.len() * 27 + 5, 1);
         let res_ptr = alloc::alloc(layout);
         let mut ptr = res_ptr;
 
@@ -278,22 +541,112 @@ mod tests {
         assert_eq!(n_to_bits2_lut(b"ATCGN"), vec![0b101110100011]);
     }
 
+    #[test]
+    fn test_n_to_bits2_into() {
+        let n = b"ATCGNATCGNATCGNATCGNATCGNATCGNATCGN";
+        let mut out = vec![!0u64; 3];
+        n_to_bits2_into(n, &mut out);
+        assert_eq!(&out[..2], &n_to_bits2_lut(n)[..]);
+        assert_eq!(out[2], !0);
+    }
+
+    #[test]
+    fn test_n_to_bits2_checked() {
+        let n = b"ATCGNATCGNATCGNATCGNATCGNATCGNatcgn";
+        assert_eq!(n_to_bits2_checked(n), Ok(n_to_bits2_lut(n)));
+        assert_eq!(n_to_bits2_checked(b"ATCGR"), Err(InvalidNucleotide{index: 4, byte: b'R'}));
+        assert_eq!(n_to_bits2_checked(b""), Ok(vec![]));
+    }
+
+    #[test]
+    fn test_concat_with_separators() {
+        let (bits, len, starts) = concat_with_separators(&[b"ATCG", b"GGA", b"ATCGATCGATCGATCGATCGATCGATCG"]);
+        assert_eq!(len, 37);
+        assert_eq!(starts, vec![0, 5, 9]);
+        assert_eq!(bits_to_n2_lut(&bits, len), b"ATCGNGGANATCGATCGATCGATCGATCGATCGATCG".to_vec());
+        assert_eq!(concat_with_separators(&[]), (vec![], 0, vec![]));
+    }
+
+    #[test]
+    fn test_mask_low_quality() {
+        let n = b"ATCGNATCGNATCGNATCGNATCGNATCGNATCGN";
+        let mut qual = vec![40u8; n.len()];
+        qual[1] = 10;
+        qual[28] = 19;
+        qual[30] = 20;
+        let bits = mask_low_quality(&n_to_bits2_lut(n), n.len(), &qual, 20);
+        assert_eq!(bits_to_n2_lut(&bits, n.len()), b"ANCGNATCGNATCGNATCGNATCGNATCNNATCGN".to_vec());
+    }
+
+    #[test]
+    fn test_n_positions() {
+        let n = b"NATCGATCGNATCGATCGATCGATCGATCNGATCGAN";
+        assert_eq!(n_positions(&n_to_bits2_lut(n), n.len()), vec![0, 9, 29, 36]);
+        assert_eq!(n_positions(&n_to_bits2_lut(n), 36), vec![0, 9, 29]);
+        assert_eq!(n_positions(&n_to_bits2_lut(b"ATCG"), 4), vec![]);
+    }
+
+    #[allow(clippy::useless_vec)]
     #[test]
     fn test_bits_to_n2_lut() {
         assert_eq!(bits_to_n2_lut(&vec![0b11011010100100010111010001111101000110110101001000101110100011, 0b1011101000111110100], 35),
                 "ATCGNATCGNATCGNATCGNATCGNATCGNATCGN".as_bytes());
+        assert_eq!(bits_to_n2_lut(&[0x7F], 3), b"NNN".to_vec());
+    }
+
+    #[test]
+    fn test_bits_to_n2_into() {
+        let n = b"ATCGNATCGNATCGNATCGNATCGNATCGNATCGN";
+        let bits = n_to_bits2_lut(n);
+        let mut out = vec![b'A'; 40];
+        bits_to_n2_into(&bits, n.len(), &mut out);
+        assert_eq!(&out[..n.len()], &n[..]);
+        assert!(out[n.len()..].iter().all(|&c| c == b'A'));
+
+        // the incomplete triplet at the end does not write past the length
+        let mut out = vec![b'A'; 4];
+        bits_to_n2_into(&bits, 4, &mut out);
+        assert_eq!(out, b"ATCG".to_vec());
+
+        // invalid chunks are decoded as `NNN`
+        let mut out = vec![b'A'; 8];
+        bits_to_n2_into(&[0x7F | (0x7F << 7) | (1 << 14)], 8, &mut out);
+        assert_eq!(out, b"NNNNNNCA".to_vec());
+    }
+
+    #[test]
+    fn test_try_bits_to_n2_lut() {
+        assert_eq!(try_bits_to_n2_lut(&[0b11011010100100010111010001111101000110110101001000101110100011, 0b1011101000111110100], 35),
+                Ok(b"ATCGNATCGNATCGNATCGNATCGNATCGNATCGN".to_vec()));
+        assert_eq!(try_bits_to_n2_lut(&[0b1111101u64 << 14], 9), Err(InvalidTriplet{index: 6, value: 125}));
+        assert_eq!(try_bits_to_n2_lut(&[0b1111111u64 << 14], 6), Ok(b"AAAAAA".to_vec()));
+    }
+
+    #[test]
+    fn test_bits_to_n2_lossy() {
+        let n = b"ATCGNATCGNATCGNATCGNATCGNATCGNATCGN";
+        let mut bits = n_to_bits2_lut(n);
+        assert_eq!(bits_to_n2_lossy(&bits, n.len()), n.to_vec());
+
+        // corrupt the second triplet
+        bits[0] |= 0b1111111 << 7;
+        assert_eq!(bits_to_n2_lossy(&bits, n.len()), b"ATCNNNTCGNATCGNATCGNATCGNATCGNATCGN".to_vec());
+        assert_eq!(bits_to_n2_lossy(&[!0u64], 5), b"NNNNN".to_vec());
     }
 
     #[test]
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
     fn test_n_to_bits2_pext() {
-        assert_eq!(n_to_bits2_pext(b"ATCGNATCGNATCGNATCGNATCGNATCGNATCGN"),
+        assert_eq!(unsafe {n_to_bits2_pext(b"ATCGNATCGNATCGNATCGNATCGNATCGNATCGN")},
                 vec![0b11011010100100010111010001111101000110110101001000101110100011, 0b1011101000111110100]);
-        assert_eq!(n_to_bits2_pext(b"ATCGN"), vec![0b101110100011]);
+        assert_eq!(unsafe {n_to_bits2_pext(b"ATCGN")}, vec![0b101110100011]);
     }
 
+    #[allow(clippy::useless_vec)]
     #[test]
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
     fn test_bits_to_n2_pdep() {
-        assert_eq!(bits_to_n2_pdep(&vec![0b11011010100100010111010001111101000110110101001000101110100011, 0b1011101000111110100], 35),
+        assert_eq!(unsafe {bits_to_n2_pdep(&vec![0b11011010100100010111010001111101000110110101001000101110100011, 0b1011101000111110100], 35)},
                 "ATCGNATCGNATCGNATCGNATCGNATCGNATCGN".as_bytes());
     }
 }
@@ -0,0 +1,74 @@
+static BYTE_LUT: [u8; 128] = {
+    let mut lut = [0u8; 128];
+    lut[b'a' as usize] = 0b1000;
+    lut[b'c' as usize] = 0b1001;
+    lut[b't' as usize] = 0b1010;
+    lut[b'u' as usize] = 0b1010;
+    lut[b'g' as usize] = 0b1011;
+    lut[b'n' as usize] = 0b1100;
+    lut[b'A' as usize] = 0b0000;
+    lut[b'C' as usize] = 0b0001;
+    lut[b'T' as usize] = 0b0010;
+    lut[b'U' as usize] = 0b0010;
+    lut[b'G' as usize] = 0b0011;
+    lut[b'N' as usize] = 0b0100;
+    lut
+};
+
+static BITS_LUT: [u8; 16] = {
+    let mut lut = [b'N'; 16];
+    lut[0b0000] = b'A';
+    lut[0b0001] = b'C';
+    lut[0b0010] = b'T';
+    lut[0b0011] = b'G';
+    lut[0b0100] = b'N';
+    lut[0b1000] = b'a';
+    lut[0b1001] = b'c';
+    lut[0b1010] = b't';
+    lut[0b1011] = b'g';
+    lut[0b1100] = b'n';
+    lut
+};
+
+/// Encode `{A, T/U, C, G, N}` from the byte string into groups of 4 bits packed into 64-bit integers, by using a
+/// naive scalar method.
+///
+/// The low 3 bits are the nucleotide (`{000, 010, 001, 011, 100}`) and the high bit is set for lowercase
+/// (soft-masked) nucleotides, so 16 nucleotides are packed into each 64-bit integer.
+pub fn n_to_bits4_lut(n: &[u8]) -> Vec<u64> {
+    let mut res = vec![0u64; (n.len() >> 4) + if n.len() & 15 == 0 {0} else {1}];
+
+    for (i, &c) in n.iter().enumerate() {
+        res[i >> 4] |= (BYTE_LUT[(c & 0b01111111) as usize] as u64) << ((i & 15) << 2);
+    }
+
+    res
+}
+
+/// Decode groups of 4 bits from packed 64-bit integers to get a byte string of `{A, T/U, C, G, N}` with the original
+/// case, by using a naive scalar method.
+pub fn bits4_to_n_lut(bits: &[u64], len: usize) -> Vec<u8> {
+    if len > (bits.len() << 4) {
+        panic!("The length is greater than the number of nucleotides!");
+    }
+
+    (0..len).map(|i| BITS_LUT[((bits[i >> 4] >> ((i & 15) << 2)) & 0b1111) as usize]).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_n_to_bits4_lut() {
+        assert_eq!(n_to_bits4_lut(b"ATCGNatcgn"), vec![0b1100101110011010100001000011000100100000]);
+        assert_eq!(n_to_bits4_lut(b"ATCGNatcgnATCGNatcgn").len(), 2);
+    }
+
+    #[test]
+    fn test_bits4_to_n_lut() {
+        let n = b"ATCGNatcgnAAaaGGccTTnnNNAtCg";
+        assert_eq!(bits4_to_n_lut(&n_to_bits4_lut(n), n.len()), n.to_vec());
+        assert_eq!(bits4_to_n_lut(&[0b1100101110011010100001000011000100100000], 10), b"ATCGNatcgn");
+    }
+}
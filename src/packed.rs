@@ -0,0 +1,500 @@
+use std::cmp::Ordering;
+
+/// Mask of the low bit of every two-bit field.
+pub(crate) const LO_BITS: u64 = 0x5555555555555555;
+/// Mask of the high bit of every two-bit field.
+pub(crate) const HI_BITS: u64 = 0xAAAAAAAAAAAAAAAA;
+
+/// Number of 64-bit words needed to hold `len` nucleotides.
+#[inline]
+pub(crate) fn words(len: usize) -> usize {
+    (len >> 5) + if len & 31 == 0 {0} else {1}
+}
+
+/// Get a mask with the low bit of each field set for the nucleotides in word `i` that are in the range `[start, end)`.
+#[inline]
+pub(crate) fn range_mask(start: usize, end: usize, i: usize) -> u64 {
+    let word_start = i << 5;
+    let lo = if start > word_start {!0u64 << ((start - word_start) << 1)} else {!0};
+    let hi = if end < word_start + 32 {(1u64 << ((end - word_start) << 1)) - 1} else {!0};
+    lo & hi & LO_BITS
+}
+
+/// Get a mask with the low bit of each field set where the field is equal to the two-bit encoding `code`.
+#[inline]
+pub(crate) fn match_fields(w: u64, code: u8) -> u64 {
+    let x = !(w ^ ((code as u64) * LO_BITS));
+    x & (x >> 1) & LO_BITS
+}
+
+/// Mask of the valid bits in the word at index `i` of a packed sequence of length `len`.
+#[inline]
+pub(crate) fn word_mask(len: usize, i: usize) -> u64 {
+    let n = len - (i << 5);
+    if n >= 32 {!0} else {(1u64 << (n << 1)) - 1}
+}
+
+/// Fold each two-bit field of `x` into its low bit, so the low bit is set where the field is nonzero.
+#[inline]
+pub(crate) fn fold_fields(x: u64) -> u64 {
+    (x | (x >> 1)) & LO_BITS
+}
+
+#[inline]
+fn check_len(bits: &[u64], len: usize) {
+    if len > (bits.len() << 5) {
+        panic!("The length is greater than the number of nucleotides!");
+    }
+}
+
+/// Check whether all of the bits after the first `len` nucleotides of a packed sequence are zero.
+///
+/// Every two-bit code is a valid nucleotide, so this is the only way to check that the packed nucleotides could have
+/// been created by this crate. Many functions assume that the padding is zero.
+pub fn assert_canonical(bits: &[u64], len: usize) -> bool {
+    check_len(bits, len);
+
+    bits.iter().enumerate().all(|(i, &w)| if (i << 5) < len {w & !word_mask(len, i) == 0} else {w == 0})
+}
+
+/// Get the two-bit encoding of the nucleotide at index `i`.
+#[inline]
+pub fn get_base(bits: &[u64], i: usize) -> u8 {
+    ((bits[i >> 5] >> ((i & 31) << 1)) & 0b11) as u8
+}
+
+/// Set the two-bit encoding of the nucleotide at index `i`.
+#[inline]
+pub(crate) fn set_base(bits: &mut [u64], i: usize, code: u8) {
+    let shift = (i & 31) << 1;
+    bits[i >> 5] = (bits[i >> 5] & !(0b11 << shift)) | ((code as u64) << shift);
+}
+
+/// Get the two-bit encoding of the complement of a nucleotide, which works the same way for both DNA and RNA.
+#[inline]
+pub fn complement_base(code: u8) -> u8 {
+    code ^ 0b10
+}
+
+/// Get the packed subsequence of length `len` that starts at index `start` of a packed sequence.
+pub fn slice_bits(bits: &[u64], start: usize, len: usize) -> Vec<u64> {
+    check_len(bits, start + len);
+
+    let offset = start >> 5;
+    let shift = (start & 31) << 1;
+    let mut res = vec![0u64; words(len)];
+
+    for i in 0..res.len() {
+        let mut w = bits[offset + i] >> shift;
+
+        if shift > 0 && offset + i + 1 < bits.len() {
+            w |= bits[offset + i + 1] << (64 - shift);
+        }
+
+        res[i] = w;
+    }
+
+    if let Some(last) = res.last_mut() {
+        *last &= word_mask(len, len.saturating_sub(1) >> 5);
+    }
+
+    res
+}
+
+/// Get the complement of a packed sequence of length `len`, without reversing it.
+///
+/// The padding after the first `len` nucleotides is zero.
+pub fn complement_bits(bits: &[u64], len: usize) -> Vec<u64> {
+    check_len(bits, len);
+    (0..words(len)).map(|i| (bits[i] ^ HI_BITS) & word_mask(len, i)).collect()
+}
+
+/// Get the reverse complement of a packed sequence of length `len`.
+pub fn revcomp_bits(bits: &[u64], len: usize) -> Vec<u64> {
+    check_len(bits, len);
+
+    let n = words(len);
+    // complement by flipping the high bit of each field, then reverse the order of the fields
+    let rev = (0..n).map(|i| reverse_fields(bits[n - 1 - i] ^ HI_BITS)).collect::<Vec<_>>();
+    // the padding at the end is now at the start
+    slice_bits(&rev, (n << 5) - len, len)
+}
+
+/// Check whether a packed sequence is the reverse complement of another packed sequence, without allocating.
+pub fn is_revcomp(a: &[u64], a_len: usize, b: &[u64], b_len: usize) -> bool {
+    check_len(a, a_len);
+    check_len(b, b_len);
+
+    if a_len != b_len {
+        return false;
+    }
+
+    for (i, &w_a) in a[..words(a_len)].iter().enumerate() {
+        let start = i << 5;
+        let n = (a_len - start).min(32);
+        // the nucleotides of `b` that are reversed into this word of `a`
+        let q = b_len - start - n;
+        let shift = (q & 31) << 1;
+        let mut w = b[q >> 5] >> shift;
+
+        if shift > 0 && (q >> 5) + 1 < b.len() {
+            w |= b[(q >> 5) + 1] << (64 - shift);
+        }
+
+        let rc = reverse_fields(w ^ HI_BITS) >> ((32 - n) << 1);
+        let mask = word_mask(a_len, i);
+
+        if (w_a ^ rc) & mask != 0 {
+            return false;
+        }
+    }
+
+    true
+}
+
+/// Append the first `src_len` nucleotides of `src` to a packed sequence of length `dst_len`.
+///
+/// The nucleotides after `dst_len` in `dst` must be zero.
+pub(crate) fn append_bits(dst: &mut Vec<u64>, dst_len: usize, src: &[u64], src_len: usize) {
+    check_len(src, src_len);
+    dst.resize(words(dst_len + src_len).max(dst.len()), 0);
+
+    let offset = dst_len >> 5;
+    let shift = (dst_len & 31) << 1;
+
+    for i in 0..words(src_len) {
+        let w = src[i] & word_mask(src_len, i);
+        dst[offset + i] |= w << shift;
+
+        if shift > 0 && offset + i + 1 < dst.len() {
+            dst[offset + i + 1] |= w >> (64 - shift);
+        }
+    }
+}
+
+/// Count the number of positions where two packed sequences of length `len` have different nucleotides.
+pub fn hamming_bits(a: &[u64], b: &[u64], len: usize) -> usize {
+    check_len(a, len);
+    check_len(b, len);
+
+    (0..words(len)).map(|i| (fold_fields(a[i] ^ b[i]) & word_mask(len, i)).count_ones() as usize).sum()
+}
+
+/// Count the number of positions where two packed sequences of length `len` have different nucleotides, counting only
+/// the positions where the low bit of the corresponding two-bit field in `site_mask` is set.
+///
+/// This is useful for comparing sequences only at known variant sites.
+pub fn hamming_masked(a: &[u64], b: &[u64], len: usize, site_mask: &[u64]) -> usize {
+    check_len(a, len);
+    check_len(b, len);
+    check_len(site_mask, len);
+
+    (0..words(len)).map(|i| (fold_fields(a[i] ^ b[i]) & site_mask[i] & word_mask(len, i)).count_ones() as usize).sum()
+}
+
+/// Get the XOR of a packed sequence of length `len` with itself shifted by `offset` nucleotides, so the field at index
+/// `i` holds `base[i] ^ base[i + offset]` for each of the first `len - offset` nucleotides.
+///
+/// A field is zero exactly when the two nucleotides are the same. The result is empty if `offset` is at least `len`.
+pub fn shifted_xor(bits: &[u64], len: usize, offset: usize) -> Vec<u64> {
+    check_len(bits, len);
+
+    if offset >= len {
+        return Vec::new();
+    }
+
+    let n = len - offset;
+    // the padding of the shifted copy is zero, so only the original needs to be masked
+    let shifted = slice_bits(bits, offset, n);
+    shifted.iter().enumerate().map(|(i, &w)| (bits[i] & word_mask(n, i)) ^ w).collect()
+}
+
+/// Compare two packed sequences of length `len` and return run-length encoded segments of matches and mismatches,
+/// where each segment is `(is_match, run_length)`.
+pub fn mismatch_runs(a: &[u64], b: &[u64], len: usize) -> Vec<(bool, usize)> {
+    check_len(a, len);
+    check_len(b, len);
+
+    let mut res: Vec<(bool, usize)> = Vec::new();
+
+    for i in 0..words(len) {
+        // low bit of each field is set if the nucleotides are different
+        let diff = fold_fields(a[i] ^ b[i]);
+        let n = (len - (i << 5)).min(32);
+        let mut j = 0;
+
+        while j < n {
+            let is_match = (diff >> (j << 1)) & 1 == 0;
+            // the run ends at the first field with the opposite state
+            let change = (if is_match {diff} else {!diff & LO_BITS}) >> (j << 1);
+            let run = if change == 0 {n - j} else {((change.trailing_zeros() >> 1) as usize).min(n - j)};
+
+            match res.last_mut() {
+                Some((m, r)) if *m == is_match => *r += run,
+                _ => res.push((is_match, run))
+            }
+
+            j += run;
+        }
+    }
+
+    res
+}
+
+/// Get the number of leading nucleotides that are the same in two packed sequences, up to `max_len`.
+pub fn common_prefix_len(a: &[u64], b: &[u64], max_len: usize) -> usize {
+    check_len(a, max_len);
+    check_len(b, max_len);
+
+    for i in 0..words(max_len) {
+        let diff = a[i] ^ b[i];
+
+        if diff != 0 {
+            // the first nucleotide is in the lowest bits, so the first mismatch is found from the trailing end
+            let prefix = (i << 5) + (diff.trailing_zeros() >> 1) as usize;
+            return prefix.min(max_len);
+        }
+    }
+
+    max_len
+}
+
+/// Change the two-bit encoding of each of the first `len` nucleotides in a packed sequence in place, so the code `c`
+/// becomes `perm[c]`.
+///
+/// This converts to and from tools that assign the two-bit codes differently, without decoding. The padding after
+/// `len` is left as zero.
+pub fn remap_codes(bits: &mut [u64], len: usize, perm: [u8; 4]) {
+    check_len(bits, len);
+
+    for (i, w) in bits[..words(len)].iter_mut().enumerate() {
+        // multiplying the low bit of each matching field by the new code fills in the whole field
+        let res = (0..4).fold(0, |acc, code| acc | (match_fields(*w, code) * ((perm[code as usize] & 0b11) as u64)));
+        *w = res & word_mask(len, i);
+    }
+}
+
+/// Compare two packed sequences lexicographically, where the nucleotides are ordered by their two-bit encodings
+/// (`A < C < T < G`), and a sequence is less than any longer sequence that it is a prefix of.
+pub fn cmp_bits(a: &[u64], a_len: usize, b: &[u64], b_len: usize) -> Ordering {
+    check_len(a, a_len);
+    check_len(b, b_len);
+
+    let len = a_len.min(b_len);
+
+    for i in 0..words(len) {
+        let diff = (a[i] ^ b[i]) & word_mask(len, i);
+
+        if diff != 0 {
+            // the first mismatch is found from the trailing end
+            let shift = diff.trailing_zeros() & !1;
+            return ((a[i] >> shift) & 0b11).cmp(&((b[i] >> shift) & 0b11));
+        }
+    }
+
+    a_len.cmp(&b_len)
+}
+
+/// Reverse the order of the two-bit fields within each 64-bit integer, without changing the order of the integers.
+///
+/// This flips the order of the nucleotides in each block of 32, which converts to and from formats that store the
+/// first nucleotide of each word in the highest bits. Unlike `u64::reverse_bits`, the two bits within each field keep
+/// their order, so each nucleotide still has the same encoding. Applying it twice gives back the original.
+pub fn reverse_word_bits(bits: &mut [u64]) {
+    for w in bits.iter_mut() {
+        *w = reverse_fields(*w);
+    }
+}
+
+#[inline]
+pub(crate) fn reverse_fields(w: u64) -> u64 {
+    let r = w.reverse_bits();
+    // swap the bits back within each field
+    ((r >> 1) & LO_BITS) | ((r & LO_BITS) << 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::n_to_bits::*;
+
+    #[test]
+    fn test_is_revcomp() {
+        let a = n_to_bits_lut(b"AATCGGATCGATCGATCGATCGATCGATCGATCGACGTT");
+        let b = n_to_bits_lut(b"AACGTCGATCGATCGATCGATCGATCGATCGATCCGATT");
+        let c = n_to_bits_lut(b"AACGTCGATCGATCGATCGATCGATCGATCGATCCGATA");
+        assert!(is_revcomp(&a, 39, &b, 39));
+        assert!(is_revcomp(&b, 39, &a, 39));
+        assert!(!is_revcomp(&a, 39, &c, 39));
+        assert!(!is_revcomp(&a, 39, &b, 38));
+        assert!(is_revcomp(&n_to_bits_lut(b"ATCG"), 4, &n_to_bits_lut(b"CGAT"), 4));
+        assert!(is_revcomp(&[], 0, &[], 0));
+
+        for len in 0..39 {
+            assert!(is_revcomp(&a, len, &revcomp_bits(&a, len), len));
+        }
+    }
+
+    #[test]
+    fn test_hamming_bits() {
+        let a = n_to_bits_lut(b"ATCGATCGATCGATCGATCGATCGATCGATCGATCG");
+        let b = n_to_bits_lut(b"ATCGTTCGATCGATCGATCGATCGATCCCTCGATCG");
+        assert_eq!(hamming_bits(&a, &b, 36), 3);
+        assert_eq!(hamming_bits(&a, &b, 27), 1);
+        assert_eq!(hamming_bits(&a, &a, 36), 0);
+        // padding is ignored
+        assert_eq!(hamming_bits(&[0], &[!0], 0), 0);
+        assert_eq!(hamming_bits(&[0], &[!0], 3), 3);
+    }
+
+    #[test]
+    fn test_hamming_masked() {
+        let a = n_to_bits_lut(b"ATCGATCGATCGATCGATCGATCGATCGATCGATCG");
+        let b = n_to_bits_lut(b"ATCGTTCGATCGATCGATCGATCGATCCCTCGATCG");
+        assert_eq!(hamming_masked(&a, &b, 36, &[!0, !0]), 3);
+        assert_eq!(hamming_masked(&a, &b, 36, &[(1 << (4 << 1)) | (1 << (28 << 1)), 0]), 2);
+        // mismatches outside the mask are ignored
+        assert_eq!(hamming_masked(&a, &b, 36, &[0b01010101, !0]), 0);
+        // only the low bit of each field is used
+        assert_eq!(hamming_masked(&a, &b, 36, &[2 << (27 << 1), 0]), 0);
+        assert_eq!(hamming_masked(&a, &b, 27, &[!0]), 1);
+    }
+
+    #[test]
+    fn test_shifted_xor() {
+        let n = b"ATCGGATCGATCCATCGATCGATCGTTCGATCGATCGATCGATCGAACGATCGATCGATCGATCGATCGAGA";
+        let bits = n_to_bits_lut(n);
+
+        for &offset in &[1, 16, 33] {
+            let res = shifted_xor(&bits, n.len(), offset);
+            let mut expected = vec![0u64; words(n.len() - offset)];
+
+            for i in 0..(n.len() - offset) {
+                set_base(&mut expected, i, get_base(&bits, i) ^ get_base(&bits, i + offset));
+            }
+
+            assert_eq!(res, expected);
+        }
+
+        assert_eq!(shifted_xor(&bits, 33, 33), vec![]);
+    }
+
+    #[test]
+    fn test_mismatch_runs() {
+        let a = n_to_bits_lut(b"ATCGATCGATCGATCGATCGATCGATCGATCGATCG");
+        let b = n_to_bits_lut(b"ATCGTTCGATCGATCGATCGATCGATCCCTCGATCG");
+        assert_eq!(mismatch_runs(&a, &b, 36), vec![(true, 4), (false, 1), (true, 22), (false, 2), (true, 7)]);
+        assert_eq!(mismatch_runs(&a, &a, 36), vec![(true, 36)]);
+        assert_eq!(mismatch_runs(&a, &b, 0), vec![]);
+    }
+
+    #[test]
+    fn test_common_prefix_len() {
+        let a = n_to_bits_lut(b"ATCGATCGATCGATCGATCGATCGATCGATCGATCG");
+        let b = n_to_bits_lut(b"ATCGAGCGATCGATCGATCGATCGATCGATCGATCG");
+        assert_eq!(common_prefix_len(&a, &b, 36), 5);
+        assert_eq!(common_prefix_len(&a, &b, 3), 3);
+        assert_eq!(common_prefix_len(&a, &a, 36), 36);
+
+        let c = n_to_bits_lut(b"ATCGATCGATCGATCGATCGATCGATCGATCGATGG");
+        assert_eq!(common_prefix_len(&a, &c, 36), 34);
+    }
+
+    #[test]
+    fn test_assert_canonical() {
+        let mut bits = n_to_bits_lut(b"ATCGATCGATCGATCGATCGATCGATCGATCGATCG");
+        assert!(assert_canonical(&bits, 36));
+        assert!(!assert_canonical(&bits, 35));
+        bits[1] |= 1 << 8;
+        assert!(!assert_canonical(&bits, 36));
+        assert!(assert_canonical(&bits, 37));
+        assert!(!assert_canonical(&[0, 1], 32));
+        assert!(assert_canonical(&[0, 0], 0));
+    }
+
+    #[test]
+    fn test_cmp_bits() {
+        let a = n_to_bits_lut(b"ATCGATCGATCGATCGATCGATCGATCGATCGATCG");
+        let b = n_to_bits_lut(b"ATCGATCGATCGATCGATCGATCGATCGATCGATGG");
+        assert_eq!(cmp_bits(&a, 36, &b, 36), Ordering::Less);
+        assert_eq!(cmp_bits(&b, 36, &a, 36), Ordering::Greater);
+        assert_eq!(cmp_bits(&a, 36, &a, 36), Ordering::Equal);
+        assert_eq!(cmp_bits(&a, 34, &b, 36), Ordering::Less);
+        assert_eq!(cmp_bits(&a, 34, &b, 34), Ordering::Equal);
+        // T is greater than C
+        assert_eq!(cmp_bits(&n_to_bits_lut(b"T"), 1, &n_to_bits_lut(b"CA"), 2), Ordering::Greater);
+        assert_eq!(cmp_bits(&[], 0, &a, 1), Ordering::Less);
+    }
+
+    #[test]
+    fn test_get_base() {
+        let bits = n_to_bits_lut(b"ATCGATCGATCGATCGATCGATCGATCGATCGATCG");
+        assert_eq!(get_base(&bits, 0), 0b00);
+        assert_eq!(get_base(&bits, 1), 0b10);
+        assert_eq!(get_base(&bits, 34), 0b01);
+        assert_eq!(get_base(&bits, 35), 0b11);
+    }
+
+    #[test]
+    fn test_remap_codes() {
+        let mut bits = n_to_bits_lut(b"ATCGATCGATCGATCGATCGATCGATCGATCGATCGG");
+        // swap A with G, and C with T
+        remap_codes(&mut bits, 37, [0b11, 0b10, 0b01, 0b00]);
+        assert_eq!(bits, n_to_bits_lut(b"GCTAGCTAGCTAGCTAGCTAGCTAGCTAGCTAGCTAA"));
+
+        let mut bits = n_to_bits_lut(b"ATCG");
+        remap_codes(&mut bits, 4, [0b01, 0b01, 0b00, 0b11]);
+        assert_eq!(bits, n_to_bits_lut(b"CACG"));
+    }
+
+    #[test]
+    fn test_reverse_word_bits() {
+        let mut bits = n_to_bits_lut(b"ATCGATCGATCGATCGATCGATCGATCGATCGATCG");
+        let orig = bits.clone();
+        reverse_word_bits(&mut bits);
+        assert_eq!(bits[0], n_to_bits_lut(b"GCTAGCTAGCTAGCTAGCTAGCTAGCTAGCTA")[0]);
+        reverse_word_bits(&mut bits);
+        assert_eq!(bits, orig);
+
+        let mut bits = vec![0b11011000];
+        reverse_word_bits(&mut bits);
+        assert_eq!(bits, vec![0b0010011100000000000000000000000000000000000000000000000000000000u64]);
+    }
+
+    #[test]
+    fn test_complement_base() {
+        assert_eq!(complement_base(0b00), 0b10);
+        assert_eq!(complement_base(0b10), 0b00);
+        assert_eq!(complement_base(0b01), 0b11);
+        assert_eq!(complement_base(0b11), 0b01);
+    }
+
+    #[test]
+    fn test_slice_bits() {
+        let n = b"ATCGGATCGATCGATCGATCGATCGATCGATCGATCGATCGATCGATCGATCGATCGATCGATCGATCGATCGA";
+        let bits = n_to_bits_lut(n);
+
+        for &(start, len) in &[(0, n.len()), (0, 0), (1, 3), (5, 32), (31, 40), (32, 32), (40, n.len() - 40)] {
+            assert_eq!(slice_bits(&bits, start, len), n_to_bits_lut(&n[start..start + len]));
+        }
+    }
+
+    #[test]
+    fn test_complement_bits() {
+        let n = b"AATCGGATCGATCGATCGATCGATCGATCGATCGAC";
+        let bits = n_to_bits_lut(n);
+        assert_eq!(complement_bits(&bits, 36), n_to_bits_lut(b"TTAGCCTAGCTAGCTAGCTAGCTAGCTAGCTAGCTG"));
+        assert_eq!(complement_bits(&bits, 4), n_to_bits_lut(b"TTAG"));
+        assert_eq!(complement_bits(&bits, 0), vec![]);
+    }
+
+    #[test]
+    fn test_revcomp_bits() {
+        let n = b"AATCGGATCGATCGATCGATCGATCGATCGATCGAC";
+        let bits = n_to_bits_lut(n);
+        assert_eq!(revcomp_bits(&bits, 36), n_to_bits_lut(b"GTCGATCGATCGATCGATCGATCGATCGATCCGATT"));
+        assert_eq!(revcomp_bits(&bits, 4), n_to_bits_lut(b"GATT"));
+        assert_eq!(revcomp_bits(&bits, 0), vec![]);
+        assert_eq!(revcomp_bits(&revcomp_bits(&bits, 36), 36), bits);
+    }
+}
@@ -0,0 +1,35 @@
+use bio::io::{fasta, fastq};
+
+use crate::n_to_bits::n_to_bits_lut;
+
+/// Encode the sequence of a FASTA record into pairs of bits packed into 64-bit integers, returning the packed
+/// sequence and its length.
+pub fn pack_record(record: &fasta::Record) -> (Vec<u64>, usize) {
+    (n_to_bits_lut(record.seq()), record.seq().len())
+}
+
+/// Encode the sequence of a FASTQ record into pairs of bits packed into 64-bit integers, returning the packed
+/// sequence and its length.
+pub fn pack_fastq_record(record: &fastq::Record) -> (Vec<u64>, usize) {
+    (n_to_bits_lut(record.seq()), record.seq().len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::n_to_bits::*;
+
+    #[test]
+    fn test_pack_record() {
+        let record = fasta::Record::with_attrs("read1", None, b"ATCGATCGATCGATCGATCGATCGATCGATCGATCG");
+        let (bits, len) = pack_record(&record);
+        assert_eq!(len, 36);
+        assert_eq!(bits_to_n_lut(&bits, len), record.seq());
+    }
+
+    #[test]
+    fn test_pack_fastq_record() {
+        let record = fastq::Record::with_attrs("read1", None, b"ATCG", b"IIII");
+        assert_eq!(pack_fastq_record(&record), (vec![0b11011000], 4));
+    }
+}
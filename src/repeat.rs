@@ -0,0 +1,162 @@
+use crate::packed::*;
+
+/// Find tandem repeats with a period of `period` nucleotides in a packed sequence, and return the start and the number
+/// of full copies of the repeat unit for each repeat with at least `min_copies` copies.
+///
+/// A repeat is a maximal run where each nucleotide is the same as the nucleotide `period` positions after it. A
+/// trailing partial copy is included in the run but not counted. A repeat with `copies` copies has at least
+/// `(copies - 1) * period` positions that match the nucleotide `period` positions after them, so the threshold is
+/// `(min_copies - 1) * period` matching positions, not `min_copies * period`.
+///
+/// Panics if `period` is zero or if `min_copies` is less than two.
+pub fn find_tandem_repeats(bits: &[u64], len: usize, period: usize, min_copies: usize) -> Vec<(usize, usize)> {
+    if len > (bits.len() << 5) {
+        panic!("The length is greater than the number of nucleotides!");
+    }
+
+    if period == 0 {
+        panic!("The period must be greater than zero!");
+    }
+
+    if min_copies < 2 {
+        panic!("The minimum number of copies must be at least two!");
+    }
+
+    if len <= period {
+        return Vec::new();
+    }
+
+    // compare each nucleotide with the nucleotide `period` positions after it
+    let n = len - period;
+    let diff = shifted_xor(bits, len, period).into_iter().map(fold_fields).collect::<Vec<_>>();
+
+    let mut res = Vec::new();
+    let mut start = 0;
+
+    for i in 0..=n {
+        if i == n || (diff[i >> 5] >> ((i & 31) << 1)) & 1 == 1 {
+            let copies = (i - start + period) / period;
+
+            if copies >= min_copies {
+                res.push((start, copies));
+            }
+
+            start = i + 1;
+        }
+    }
+
+    res
+}
+
+/// Homopolymer compress a packed sequence by collapsing each run of the same nucleotide into a single nucleotide.
+///
+/// Returns the compressed packed sequence, its length, and the length of the run for each nucleotide in it.
+pub fn homopolymer_compress(bits: &[u64], len: usize) -> (Vec<u64>, usize, Vec<u32>) {
+    if len > (bits.len() << 5) {
+        panic!("The length is greater than the number of nucleotides!");
+    }
+
+    if len == 0 {
+        return (Vec::new(), 0, Vec::new());
+    }
+
+    // a nonzero field means that a run ends at that nucleotide
+    let diff = shifted_xor(bits, len, 1).into_iter().map(fold_fields).collect::<Vec<_>>();
+    let mut res = Vec::new();
+    let mut runs = Vec::new();
+    let mut start = 0;
+
+    for i in 0..len {
+        if i == len - 1 || (diff[i >> 5] >> ((i & 31) << 1)) & 1 == 1 {
+            res.resize(words(runs.len() + 1), 0);
+            set_base(&mut res, runs.len(), get_base(bits, i));
+            runs.push((i + 1 - start) as u32);
+            start = i + 1;
+        }
+    }
+
+    let res_len = runs.len();
+    (res, res_len, runs)
+}
+
+/// Expand a packed sequence that was compressed by `homopolymer_compress`, by repeating each nucleotide by its run
+/// length.
+///
+/// Returns the expanded packed sequence and its length.
+pub fn homopolymer_expand(compressed_bits: &[u64], compressed_len: usize, run_lengths: &[u32]) -> (Vec<u64>, usize) {
+    if compressed_len > (compressed_bits.len() << 5) {
+        panic!("The length is greater than the number of nucleotides!");
+    }
+
+    if run_lengths.len() != compressed_len {
+        panic!("The number of run lengths is not equal to the number of nucleotides!");
+    }
+
+    let total = run_lengths.iter().map(|&r| r as usize).sum();
+    let mut res = Vec::with_capacity(words(total));
+    let mut len = 0;
+
+    for (i, &run) in run_lengths.iter().enumerate() {
+        // a whole word of the repeated nucleotide
+        let pattern = [(get_base(compressed_bits, i) as u64) * LO_BITS];
+        let mut run = run as usize;
+
+        while run > 0 {
+            let n = run.min(32);
+            append_bits(&mut res, len, &pattern, n);
+            len += n;
+            run -= n;
+        }
+    }
+
+    (res, len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::n_to_bits::*;
+
+    #[test]
+    fn test_find_tandem_repeats() {
+        let n = b"GGATCACACACACACATGCATGCATGCAATCG";
+        let bits = n_to_bits_lut(n);
+        assert_eq!(find_tandem_repeats(&bits, n.len(), 2, 3), vec![(4, 6)]);
+        assert_eq!(find_tandem_repeats(&bits, n.len(), 4, 3), vec![(4, 3), (14, 3)]);
+        assert_eq!(find_tandem_repeats(&bits, n.len(), 2, 7), vec![]);
+        // 10 matching positions is exactly `(6 - 1) * 2`
+        assert_eq!(find_tandem_repeats(&bits, n.len(), 2, 6), vec![(4, 6)]);
+        assert_eq!(find_tandem_repeats(&bits, n.len(), 1, 2), vec![(0, 2), (27, 2)]);
+        assert_eq!(find_tandem_repeats(&bits, 2, 2, 2), vec![]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_find_tandem_repeats_one_copy() {
+        find_tandem_repeats(&n_to_bits_lut(b"ATATAT"), 6, 2, 1);
+    }
+
+    #[test]
+    fn test_homopolymer_compress() {
+        let (bits, len, runs) = homopolymer_compress(&n_to_bits_lut(b"AAACCGT"), 7);
+        assert_eq!((bits, len, runs), (n_to_bits_lut(b"ACGT"), 4, vec![3, 2, 1, 1]));
+
+        let n = [b"A".repeat(40), b"TTG".to_vec(), b"C".repeat(33), b"ATATAT".to_vec()].concat();
+        let (bits, len, runs) = homopolymer_compress(&n_to_bits_lut(&n), n.len());
+        assert_eq!((bits, len), (n_to_bits_lut(b"ATGCATATAT"), 10));
+        assert_eq!(runs, vec![40, 2, 1, 33, 1, 1, 1, 1, 1, 1]);
+
+        assert_eq!(homopolymer_compress(&[], 0), (vec![], 0, vec![]));
+    }
+
+    #[test]
+    fn test_homopolymer_expand() {
+        assert_eq!(homopolymer_expand(&n_to_bits_lut(b"ACGT"), 4, &[3, 2, 1, 1]), (n_to_bits_lut(b"AAACCGT"), 7));
+
+        let n = [b"A".repeat(40), b"TTG".to_vec(), b"C".repeat(70), b"ATATAT".to_vec(), b"G".repeat(31)].concat();
+        let (bits, len, runs) = homopolymer_compress(&n_to_bits_lut(&n), n.len());
+        assert_eq!(homopolymer_expand(&bits, len, &runs), (n_to_bits_lut(&n), n.len()));
+
+        assert_eq!(homopolymer_expand(&[], 0, &[]), (vec![], 0));
+    }
+}
@@ -0,0 +1,155 @@
+use std::collections::HashMap;
+
+use crate::kmer::*;
+use crate::packed::*;
+
+/// Index of the positions of every k-mer in a packed reference sequence.
+#[derive(Debug, Clone)]
+pub struct KmerIndex {
+    bits: Vec<u64>,
+    len: usize,
+    k: usize,
+    positions: HashMap<u64, Vec<usize>>
+}
+
+impl KmerIndex {
+    /// Build an index of the k-mers of the first `len` nucleotides in `bits`.
+    pub fn new(bits: &[u64], len: usize, k: usize) -> Self {
+        let mut positions = HashMap::new();
+
+        for (i, kmer) in KmerIter::new(bits, len, k).enumerate() {
+            positions.entry(kmer).or_insert_with(Vec::new).push(i);
+        }
+
+        KmerIndex{bits: slice_bits(bits, 0, len), len, k, positions}
+    }
+
+    /// Get the positions of a packed k-mer in the reference, in increasing order.
+    pub fn get(&self, kmer: u64) -> &[usize] {
+        self.positions.get(&kmer).map(|p| p.as_slice()).unwrap_or(&[])
+    }
+
+    /// Get the length of the k-mers.
+    pub fn k(&self) -> usize {
+        self.k
+    }
+}
+
+/// Find the maximal exact matches between a packed query sequence and the reference of a k-mer index that have a
+/// length of at least `min_len`.
+///
+/// Each match is `(query_pos, ref_pos, match_len)`, and matches cannot be extended in either direction. Matches are
+/// seeded by k-mers, so matches shorter than `k` are not found.
+pub fn find_mems(bits: &[u64], len: usize, index: &KmerIndex, min_len: usize) -> Vec<(usize, usize, usize)> {
+    let k = index.k;
+    let mut res = Vec::new();
+
+    for (q, kmer) in KmerIter::new(bits, len, k).enumerate() {
+        for &r in index.get(kmer) {
+            // only start from the leftmost position of each match
+            if q > 0 && r > 0 && get_base(bits, q - 1) == get_base(&index.bits, r - 1) {
+                continue;
+            }
+
+            // extend to the right after the k-mer
+            let max_ext = (len - q - k).min(index.len - r - k);
+            let match_len = k + extend_len(bits, q + k, &index.bits, r + k, max_ext);
+
+            if match_len >= min_len {
+                res.push((q, r, match_len));
+            }
+        }
+    }
+
+    res
+}
+
+// the 32 nucleotides starting at index `pos`, where nucleotides past the end of `bits` are zero
+#[inline]
+fn window(bits: &[u64], pos: usize) -> u64 {
+    let offset = pos >> 5;
+    let shift = (pos & 31) << 1;
+    let mut w = bits[offset] >> shift;
+
+    if shift > 0 && offset + 1 < bits.len() {
+        w |= bits[offset + 1] << (64 - shift);
+    }
+
+    w
+}
+
+// number of matching nucleotides (up to `max_len`) starting at `a_pos` in `a` and `b_pos` in `b`, compared in place
+fn extend_len(a: &[u64], a_pos: usize, b: &[u64], b_pos: usize, max_len: usize) -> usize {
+    let mut i = 0;
+
+    while i < max_len {
+        let diff = window(a, a_pos + i) ^ window(b, b_pos + i);
+
+        if diff != 0 {
+            // the first nucleotide is in the lowest bits, so the first mismatch is found from the trailing end
+            return (i + (diff.trailing_zeros() >> 1) as usize).min(max_len);
+        }
+
+        i += 32;
+    }
+
+    max_len
+}
+
+/// Find the anchors between two packed sequences, which are the pairs `(a_pos, b_pos)` where the k-mers starting at
+/// `a_pos` in `a` and `b_pos` in `b` are the same.
+///
+/// Only forward strand k-mers are compared. The anchors are sorted by `a_pos`, then `b_pos`.
+pub fn shared_anchors(a_bits: &[u64], a_len: usize, b_bits: &[u64], b_len: usize, k: usize) -> Vec<(usize, usize)> {
+    let index = KmerIndex::new(b_bits, b_len, k);
+    let mut res = Vec::new();
+
+    for (i, kmer) in KmerIter::new(a_bits, a_len, k).enumerate() {
+        res.extend(index.get(kmer).iter().map(|&j| (i, j)));
+    }
+
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::n_to_bits::*;
+
+    #[test]
+    fn test_kmer_index() {
+        let bits = n_to_bits_lut(b"ATCGATCGGA");
+        let index = KmerIndex::new(&bits, 10, 4);
+        assert_eq!(index.get(n_to_bits_lut(b"ATCG")[0]), &[0, 4]);
+        assert_eq!(index.get(n_to_bits_lut(b"CGGA")[0]), &[6]);
+        assert_eq!(index.get(n_to_bits_lut(b"AAAA")[0]), &[] as &[usize]);
+    }
+
+    #[test]
+    fn test_find_mems() {
+        let reference = b"TTTTTTTTGGCATCGATCGGACTAGCATTTTTTTTTTTTTTTTTTTT";
+        let query = b"CCCGCATCGATCGGACTAGCCCCC";
+        let index = KmerIndex::new(&n_to_bits_lut(reference), reference.len(), 5);
+        let mems = find_mems(&n_to_bits_lut(query), query.len(), &index, 10);
+        assert_eq!(mems, vec![(3, 9, 17)]);
+        assert_eq!(&query[3..20], &reference[9..26]);
+
+        assert_eq!(find_mems(&n_to_bits_lut(query), query.len(), &index, 18), vec![]);
+
+        // the match spans several words and ends at the end of the query
+        let reference = [b"CCCCCCC".to_vec(), b"ATCGGATC".repeat(12), b"AAAA".to_vec()].concat();
+        let query = [b"GG".to_vec(), b"ATCGGATC".repeat(12)].concat();
+        let index = KmerIndex::new(&n_to_bits_lut(&reference), reference.len(), 8);
+        let mems = find_mems(&n_to_bits_lut(&query), query.len(), &index, 90);
+        assert_eq!(mems, vec![(2, 7, 96)]);
+    }
+
+    #[test]
+    fn test_shared_anchors() {
+        let a = b"TTTTTGCATTTTT";
+        let b = b"CCGCATCCCCCCCGCAT";
+        assert_eq!(shared_anchors(&n_to_bits_lut(a), a.len(), &n_to_bits_lut(b), b.len(), 4), vec![(5, 2), (5, 13)]);
+        assert_eq!(shared_anchors(&n_to_bits_lut(a), a.len(), &n_to_bits_lut(b), b.len(), 5), vec![]);
+        assert_eq!(shared_anchors(&n_to_bits_lut(a), a.len(), &n_to_bits_lut(a), a.len(), 13), vec![(0, 0)]);
+    }
+}
@@ -0,0 +1,257 @@
+use std::convert::TryInto;
+#[cfg(feature = "memmap2")]
+use std::fs::OpenOptions;
+#[cfg(feature = "memmap2")]
+use std::io;
+#[cfg(feature = "memmap2")]
+use std::path::Path;
+
+use crate::n_to_bits::n_to_bits_lut;
+#[cfg(feature = "memmap2")]
+use crate::n_to_bits::n_to_bits_lut_reuse;
+
+/// Encoding scheme of packed nucleotides.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scheme {
+    /// Two bits per nucleotide, from `n_to_bits`.
+    TwoBit,
+    /// Triplets of `{A, T/U, C, G, N}` in 7 bits, from `n_to_bits2`.
+    Base5,
+    /// Four bits per nucleotide with a lowercase flag, from `n_to_bits4`.
+    FourBit
+}
+
+impl Scheme {
+    fn tag(self) -> u8 {
+        match self {
+            Scheme::TwoBit => 2,
+            Scheme::Base5 => 5,
+            Scheme::FourBit => 4
+        }
+    }
+
+    fn from_tag(tag: u8) -> Option<Self> {
+        match tag {
+            2 => Some(Scheme::TwoBit),
+            5 => Some(Scheme::Base5),
+            4 => Some(Scheme::FourBit),
+            _ => None
+        }
+    }
+
+    /// Get the number of 64-bit integers needed to hold `len` nucleotides.
+    #[allow(clippy::manual_is_multiple_of)]
+    pub fn words(self, len: usize) -> usize {
+        let per_word = match self {
+            Scheme::TwoBit => 32,
+            Scheme::Base5 => 27,
+            Scheme::FourBit => 16
+        };
+        len / per_word + if len % per_word == 0 {0} else {1}
+    }
+}
+
+/// Length of the header, which is the scheme tag followed by the number of nucleotides as a little-endian 64-bit
+/// integer.
+pub const HEADER_LEN: usize = 9;
+
+/// Serialize packed nucleotides into bytes, with a header that stores the scheme and the number of nucleotides,
+/// followed by the 64-bit integers in little-endian byte order.
+pub fn to_packed_bytes(bits: &[u64], len: usize, scheme: Scheme) -> Vec<u8> {
+    to_bytes(bits, len, scheme, false)
+}
+
+/// Serialize packed nucleotides into bytes like `to_packed_bytes`, but with the number of nucleotides and the 64-bit
+/// integers in big-endian byte order.
+pub fn to_packed_bytes_be(bits: &[u64], len: usize, scheme: Scheme) -> Vec<u8> {
+    to_bytes(bits, len, scheme, true)
+}
+
+fn to_bytes(bits: &[u64], len: usize, scheme: Scheme, big_endian: bool) -> Vec<u8> {
+    let words = scheme.words(len);
+
+    if words > bits.len() {
+        panic!("The length is greater than the number of nucleotides!");
+    }
+
+    let to_bytes = if big_endian {u64::to_be_bytes} else {u64::to_le_bytes};
+    let mut res = Vec::with_capacity(HEADER_LEN + (words << 3));
+    res.push(scheme.tag());
+    res.extend_from_slice(&to_bytes(len as u64));
+
+    for &w in &bits[..words] {
+        res.extend_from_slice(&to_bytes(w));
+    }
+
+    res
+}
+
+/// Deserialize packed nucleotides from bytes created by `to_packed_bytes`, returning the scheme, the 64-bit integers,
+/// and the number of nucleotides.
+///
+/// Returns `None` if the bytes are not in the right format.
+pub fn from_packed_bytes(bytes: &[u8]) -> Option<(Scheme, Vec<u64>, usize)> {
+    from_bytes(bytes, false)
+}
+
+/// Deserialize packed nucleotides from bytes created by `to_packed_bytes_be`, returning the scheme, the 64-bit
+/// integers, and the number of nucleotides.
+///
+/// Returns `None` if the bytes are not in the right format.
+pub fn from_packed_bytes_be(bytes: &[u8]) -> Option<(Scheme, Vec<u64>, usize)> {
+    from_bytes(bytes, true)
+}
+
+fn from_bytes(bytes: &[u8], big_endian: bool) -> Option<(Scheme, Vec<u64>, usize)> {
+    let scheme = detect_scheme(bytes)?;
+    let from_bytes = if big_endian {u64::from_be_bytes} else {u64::from_le_bytes};
+    let len = from_bytes(bytes[1..HEADER_LEN].try_into().unwrap()) as usize;
+    let body = &bytes[HEADER_LEN..];
+
+    if body.len() != (scheme.words(len) << 3) {
+        return None;
+    }
+
+    let bits = body.chunks_exact(8).map(|b| from_bytes(b.try_into().unwrap())).collect();
+    Some((scheme, bits, len))
+}
+
+/// Encode `{A, T/U, C, G}` from the byte string into pairs of bits packed into 64-bit integers, and write them to a
+/// memory mapped file at `path` in the same format as `to_packed_bytes` with `Scheme::TwoBit`.
+///
+/// The file is created or truncated to the exact size of the output, and the nucleotides are encoded in chunks directly
+/// into the mapping, so the whole packed output is never stored in memory.
+#[cfg(feature = "memmap2")]
+pub fn encode_to_mmap(n: &[u8], path: &Path) -> io::Result<()> {
+    const CHUNK: usize = 1 << 16;
+
+    let words = Scheme::TwoBit.words(n.len());
+    let file = OpenOptions::new().read(true).write(true).create(true).truncate(true).open(path)?;
+    file.set_len((HEADER_LEN + (words << 3)) as u64)?;
+
+    let mut mmap = unsafe {memmap2::MmapMut::map_mut(&file)?};
+    mmap[0] = Scheme::TwoBit.tag();
+    mmap[1..HEADER_LEN].copy_from_slice(&(n.len() as u64).to_le_bytes());
+
+    let mut buf = Vec::with_capacity(CHUNK >> 5);
+
+    for (i, chunk) in n.chunks(CHUNK).enumerate() {
+        n_to_bits_lut_reuse(chunk, &mut buf);
+        let offset = HEADER_LEN + i * (CHUNK >> 2);
+
+        for (j, w) in buf.iter().enumerate() {
+            mmap[(offset + (j << 3))..(offset + (j << 3) + 8)].copy_from_slice(&w.to_le_bytes());
+        }
+    }
+
+    mmap.flush()
+}
+
+/// Get the scheme of packed nucleotides that were serialized into bytes, or `None` if the header is invalid.
+pub fn detect_scheme(bytes: &[u8]) -> Option<Scheme> {
+    if bytes.len() < HEADER_LEN {
+        return None;
+    }
+
+    Scheme::from_tag(bytes[0])
+}
+
+/// Get the checksum of a 64-bit integer, which is the XOR of its 8 bytes.
+#[inline]
+pub fn word_checksum(w: u64) -> u8 {
+    w.to_le_bytes().iter().fold(0, |acc, &b| acc ^ b)
+}
+
+/// Encode `{A, T/U, C, G}` from the byte string into pairs of bits packed into 64-bit integers, and return the 64-bit
+/// integers, the checksum of each 64-bit integer from `word_checksum`, and the number of nucleotides.
+///
+/// Storing the checksums makes it possible to find which 64-bit integers were corrupted, since any single bit flip
+/// changes the checksum.
+pub fn encode_with_word_checksums(n: &[u8]) -> (Vec<u64>, Vec<u8>, usize) {
+    let bits = n_to_bits_lut(n);
+    let checksums = bits.iter().map(|&w| word_checksum(w)).collect();
+    (bits, checksums, n.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::n_to_bits2::*;
+    use crate::n_to_bits4::*;
+
+    #[test]
+    fn test_to_packed_bytes() {
+        let bytes = to_packed_bytes(&[0b11011000], 4, Scheme::TwoBit);
+        assert_eq!(bytes, vec![2, 4, 0, 0, 0, 0, 0, 0, 0, 0b11011000, 0, 0, 0, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_from_packed_bytes() {
+        let n = b"ATCGNATCGNATCGNATCGNATCGNATCGNATCGN";
+        let bits = n_to_bits2_lut(n);
+        let bytes = to_packed_bytes(&bits, n.len(), Scheme::Base5);
+        assert_eq!(from_packed_bytes(&bytes), Some((Scheme::Base5, bits, n.len())));
+
+        assert_eq!(from_packed_bytes(&bytes[..bytes.len() - 1]), None);
+        assert_eq!(from_packed_bytes(&[2, 0, 0, 0, 0, 0, 0, 0, 0]), Some((Scheme::TwoBit, vec![], 0)));
+    }
+
+    #[test]
+    fn test_to_packed_bytes_be() {
+        let bytes = to_packed_bytes_be(&[0b11011000], 4, Scheme::TwoBit);
+        assert_eq!(bytes, vec![2, 0, 0, 0, 0, 0, 0, 0, 4, 0, 0, 0, 0, 0, 0, 0, 0b11011000]);
+        assert_ne!(bytes, to_packed_bytes(&[0b11011000], 4, Scheme::TwoBit));
+    }
+
+    #[test]
+    fn test_from_packed_bytes_be() {
+        let n = b"ATCGATCGATCGATCGATCGATCGATCGATCGGGCCATCG";
+        let bits = n_to_bits_lut(n);
+        let bytes = to_packed_bytes_be(&bits, n.len(), Scheme::TwoBit);
+        assert_eq!(from_packed_bytes_be(&bytes), Some((Scheme::TwoBit, bits.clone(), n.len())));
+        assert_ne!(from_packed_bytes(&bytes), Some((Scheme::TwoBit, bits, n.len())));
+        assert_eq!(from_packed_bytes_be(&bytes[..bytes.len() - 1]), None);
+    }
+
+    #[cfg(feature = "memmap2")]
+    #[test]
+    fn test_encode_to_mmap() {
+        let path = std::env::temp_dir().join(format!("cute_nucleotides_test_{}.bin", std::process::id()));
+        let n = b"ATCGATCGATCGATCGATCGATCGATCGATCGGGCCATCG".repeat(5000);
+        encode_to_mmap(&n, &path).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(bytes, to_packed_bytes(&n_to_bits_lut(&n), n.len(), Scheme::TwoBit));
+    }
+
+    #[test]
+    fn test_detect_scheme() {
+        let n = b"ATCGNatcgn";
+        assert_eq!(detect_scheme(&to_packed_bytes(&n_to_bits_lut(n), n.len(), Scheme::TwoBit)), Some(Scheme::TwoBit));
+        assert_eq!(detect_scheme(&to_packed_bytes(&n_to_bits2_lut(n), n.len(), Scheme::Base5)), Some(Scheme::Base5));
+        assert_eq!(detect_scheme(&to_packed_bytes(&n_to_bits4_lut(n), n.len(), Scheme::FourBit)), Some(Scheme::FourBit));
+        assert_eq!(detect_scheme(&[0; 9]), None);
+        assert_eq!(detect_scheme(&[2]), None);
+    }
+
+    #[test]
+    fn test_word_checksum() {
+        assert_eq!(word_checksum(0), 0);
+        assert_eq!(word_checksum(0x0102040810204080), 0xFF);
+        assert_eq!(word_checksum(0x0101), 0);
+    }
+
+    #[test]
+    fn test_encode_with_word_checksums() {
+        let n = b"ATCGATCGATCGATCGATCGATCGATCGATCGGGCCATCGATCGATCGATCGATCGATCGATCGATCGATCGATCGTT";
+        let (mut bits, checksums, len) = encode_with_word_checksums(n);
+        assert_eq!(len, n.len());
+        assert_eq!(bits, n_to_bits_lut(n));
+        assert_eq!(checksums.len(), bits.len());
+
+        bits[1] ^= 1 << 37;
+        let corrupted = (0..bits.len()).filter(|&i| word_checksum(bits[i]) != checksums[i]).collect::<Vec<_>>();
+        assert_eq!(corrupted, vec![1]);
+    }
+}
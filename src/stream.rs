@@ -0,0 +1,145 @@
+use std::io::{self, Write};
+
+use crate::n_to_bits::{BITS_LUT, bits_to_n_overwrite};
+use crate::packed::get_base;
+
+/// Size of the stack buffer used by `decode_chunks`.
+pub const MAX_CHUNK: usize = 4096;
+
+/// Decode `len` nucleotides packed in 64-bit integers in chunks of around `chunk` nucleotides, and write each decoded
+/// chunk of `{A, T/U, C, G}` bytes to `w`.
+///
+/// The chunk size is rounded up to a multiple of 32 so each chunk starts at the beginning of a 64-bit integer. Only
+/// one chunk is decoded in memory at a time, and every chunk is decoded into the same buffer.
+pub fn decode_to_writer<W: Write>(bits: &[u64], len: usize, w: &mut W, chunk: usize) -> io::Result<()> {
+    if len > (bits.len() << 5) {
+        panic!("The length is greater than the number of nucleotides!");
+    }
+
+    if chunk == 0 {
+        panic!("The chunk size must be greater than zero!");
+    }
+
+    let chunk_words = (chunk >> 5) + if chunk & 31 == 0 {0} else {1};
+    let mut buf = vec![0u8; (chunk_words << 5).min(len)];
+    let mut start = 0;
+
+    while start < len {
+        let n = (len - start).min(chunk_words << 5);
+        bits_to_n_overwrite(&bits[(start >> 5)..], n, &mut buf);
+        w.write_all(&buf[..n])?;
+        start += n;
+    }
+
+    Ok(())
+}
+
+/// Decode `len` nucleotides packed in 64-bit integers in chunks of around `chunk` nucleotides, and call `f` with each
+/// decoded chunk of `{A, T/U, C, G}` bytes.
+///
+/// The chunk size is rounded up to a multiple of 32, like in `decode_to_writer`, and it must not be greater than
+/// `MAX_CHUNK`. Every chunk is decoded into the same buffer on the stack, so nothing is allocated.
+pub fn decode_chunks<F: FnMut(&[u8])>(bits: &[u64], len: usize, chunk: usize, mut f: F) {
+    if len > (bits.len() << 5) {
+        panic!("The length is greater than the number of nucleotides!");
+    }
+
+    if chunk == 0 {
+        panic!("The chunk size must be greater than zero!");
+    }
+
+    if chunk > MAX_CHUNK {
+        panic!("The chunk size is greater than the maximum chunk size!");
+    }
+
+    let chunk_words = (chunk >> 5) + if chunk & 31 == 0 {0} else {1};
+    let mut buf = [0u8; MAX_CHUNK];
+    let mut start = 0;
+
+    while start < len {
+        let n = (len - start).min(chunk_words << 5);
+        bits_to_n_overwrite(&bits[(start >> 5)..], n, &mut buf);
+        f(&buf[..n]);
+        start += n;
+    }
+}
+
+/// Iterator that lazily decodes a packed sequence into `{A, T/U, C, G}` bytes, one nucleotide at a time.
+pub struct ByteIter<'a> {
+    bits: &'a [u64],
+    len: usize,
+    i: usize
+}
+
+impl<'a> ByteIter<'a> {
+    /// Create an iterator over the first `len` nucleotides in `bits`.
+    pub fn new(bits: &'a [u64], len: usize) -> Self {
+        if len > (bits.len() << 5) {
+            panic!("The length is greater than the number of nucleotides!");
+        }
+
+        ByteIter{bits, len, i: 0}
+    }
+}
+
+impl<'a> Iterator for ByteIter<'a> {
+    type Item = u8;
+
+    #[inline]
+    fn next(&mut self) -> Option<u8> {
+        if self.i >= self.len {
+            return None;
+        }
+
+        let c = BITS_LUT[get_base(self.bits, self.i) as usize];
+        self.i += 1;
+        Some(c)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let n = self.len - self.i;
+        (n, Some(n))
+    }
+}
+
+impl<'a> ExactSizeIterator for ByteIter<'a> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::n_to_bits::*;
+
+    #[test]
+    fn test_decode_to_writer() {
+        let n = b"ATCGATCGATCGATCGATCGATCGATCGATCGGGCCATCGATCGATCGATCGATCGATCGATCGATCGATCGATCGTT".repeat(3);
+        let bits = n_to_bits_lut(&n);
+
+        for &chunk in &[1, 32, 33, 100, 1000] {
+            let mut w = io::Cursor::new(Vec::new());
+            decode_to_writer(&bits, n.len() - 3, &mut w, chunk).unwrap();
+            assert_eq!(w.into_inner(), bits_to_n_lut(&bits, n.len() - 3));
+        }
+    }
+
+    #[test]
+    fn test_decode_chunks() {
+        let n = b"ATCGATCGATCGATCGATCGATCGATCGATCGGGCCATCGATCGATCGATCGATCGATCGATCGATCGATCGATCGTT".repeat(3);
+        let bits = n_to_bits_lut(&n);
+
+        for &chunk in &[1, 32, 33, 100, MAX_CHUNK] {
+            let mut res = Vec::new();
+            decode_chunks(&bits, n.len() - 3, chunk, |c| res.extend_from_slice(c));
+            assert_eq!(res, bits_to_n_lut(&bits, n.len() - 3));
+        }
+    }
+
+    #[test]
+    fn test_byte_iter() {
+        let n = b"ATCGATCGATCGATCGATCGATCGATCGATCGGGCCATCGT";
+        let bits = n_to_bits_lut(n);
+        assert_eq!(ByteIter::new(&bits, n.len()).collect::<Vec<_>>(), bits_to_n_lut(&bits, n.len()));
+        assert_eq!(ByteIter::new(&bits, 35).len(), 35);
+        assert_eq!(ByteIter::new(&bits, 0).next(), None);
+    }
+}
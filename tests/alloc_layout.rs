@@ -0,0 +1,115 @@
+// the vectorized methods only exist on x86
+#![cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::process;
+
+use cute_nucleotides::n_to_bits::*;
+use cute_nucleotides::n_to_bits2::*;
+
+// the size and alignment of each allocation are stored right before it, so deallocating with a different layout
+// than the one used for allocating can be caught, and allocating zero bytes is also caught
+struct LayoutChecker;
+
+const HEADER: usize = 16;
+
+unsafe impl GlobalAlloc for LayoutChecker {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        if layout.size() == 0 {
+            process::abort();
+        }
+
+        let offset = layout.align().max(HEADER);
+        let ptr = unsafe {System.alloc(Layout::from_size_align_unchecked(layout.size() + offset, offset))};
+
+        if ptr.is_null() {
+            return ptr;
+        }
+
+        unsafe {
+            let res = ptr.add(offset);
+            *(res.sub(16) as *mut usize) = layout.size();
+            *(res.sub(8) as *mut usize) = layout.align();
+            res
+        }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe {
+            let size = *(ptr.sub(16) as *const usize);
+            let align = *(ptr.sub(8) as *const usize);
+
+            // unwinding out of the allocator is not allowed
+            if size != layout.size() || align != layout.align() {
+                process::abort();
+            }
+
+            let offset = align.max(HEADER);
+            System.dealloc(ptr.sub(offset), Layout::from_size_align_unchecked(size + offset, offset));
+        }
+    }
+}
+
+#[global_allocator]
+static ALLOC: LayoutChecker = LayoutChecker;
+
+#[test]
+fn test_bits_to_n_layout() {
+    let n = b"ATCGATCGATCGATCGATCGATCGATCGATCGATCGA";
+    let bits = n_to_bits_lut(n);
+
+    if is_x86_feature_detected!("avx2") {
+        assert_eq!(unsafe {bits_to_n_shuffle(&bits, n.len())}, n.to_vec());
+    }
+
+    if is_x86_feature_detected!("avx2") && is_x86_feature_detected!("bmi2") {
+        assert_eq!(unsafe {bits_to_n_pdep(&bits, n.len())}, n.to_vec());
+    }
+
+    if is_x86_feature_detected!("ssse3") && is_x86_feature_detected!("pclmulqdq") {
+        assert_eq!(unsafe {bits_to_n_clmul(&bits, n.len())}, n.to_vec());
+    }
+}
+
+#[test]
+fn test_bits_to_n2_layout() {
+    let n = b"ATCGNATCGNATCGNATCGNATCGNATCGNATCGN";
+    let bits = n_to_bits2_lut(n);
+
+    if is_x86_feature_detected!("avx2") && is_x86_feature_detected!("bmi2") {
+        assert_eq!(unsafe {bits_to_n2_pdep(&bits, n.len())}, n.to_vec());
+    }
+}
+
+#[test]
+fn test_empty_layout() {
+    assert!(bits_to_n_lut(&[], 0).is_empty());
+    assert!(n_to_bits_lut(b"").is_empty());
+
+    if is_x86_feature_detected!("avx2") {
+        assert!(unsafe {n_to_bits_shift(b"")}.is_empty());
+        assert!(unsafe {n_to_bits_movemask(b"")}.is_empty());
+        assert!(unsafe {n_to_bits_mul(b"")}.is_empty());
+        assert!(unsafe {bits_to_n_shuffle(&[], 0)}.is_empty());
+    }
+
+    if is_x86_feature_detected!("avx2") && is_x86_feature_detected!("bmi2") {
+        assert!(unsafe {n_to_bits_pext(b"")}.is_empty());
+        assert!(unsafe {bits_to_n_pdep(&[], 0)}.is_empty());
+        assert!(unsafe {n_to_bits2_pext(b"")}.is_empty());
+        assert!(unsafe {bits_to_n2_pdep(&[], 0)}.is_empty());
+    }
+
+    if is_x86_feature_detected!("ssse3") && is_x86_feature_detected!("pclmulqdq") {
+        assert!(unsafe {bits_to_n_clmul(&[], 0)}.is_empty());
+    }
+
+    assert!(bits_to_n2_lut(&[], 0).is_empty());
+    assert!(n_to_bits2_lut(b"").is_empty());
+    assert_eq!(n_to_bits_with_mask(b""), (vec![], vec![]));
+    assert!(bits_to_n_with_mask(&[], &[], 0).is_empty());
+    assert_eq!(decode_with_complement(&[], 0), (vec![], vec![]));
+    assert_eq!(canonical_sequence(b""), (vec![], 0, false));
+    assert_eq!(n_to_bits_checked(b""), Ok(vec![]));
+    assert_eq!(n_to_bits2_checked(b""), Ok(vec![]));
+}